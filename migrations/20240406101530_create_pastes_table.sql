@@ -0,0 +1,12 @@
+-- Pastes that are stored by the application.
+-- Pastes can be created anonymously, in which case there is no owner.
+CREATE TABLE IF NOT EXISTS pastr.pastes (
+    id uuid PRIMARY KEY,
+    owner uuid REFERENCES pastr.users(id) ON DELETE CASCADE,
+    title TEXT,
+    content TEXT NOT NULL,
+    language TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS pastes_owner_idx ON pastr.pastes(owner);
@@ -0,0 +1,6 @@
+-- Hash of the secret token that allows modifying or deleting a paste.
+-- The token itself is only handed out once when the paste is created.
+ALTER TABLE pastr.pastes
+ADD COLUMN edit_token_hash TEXT NOT NULL DEFAULT '';
+ALTER TABLE pastr.pastes
+ALTER COLUMN edit_token_hash DROP DEFAULT;
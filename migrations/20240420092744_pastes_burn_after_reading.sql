@@ -0,0 +1,3 @@
+-- Pastes that get deleted the first time they are read.
+ALTER TABLE pastr.pastes
+ADD COLUMN burn_after_reading BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,9 @@
+-- Visibility of a paste. Public pastes are listed, unlisted pastes are only reachable via their url
+-- and private pastes are only visible to their owner. Existing pastes were never listed, so they become unlisted.
+ALTER TABLE pastr.pastes
+ADD COLUMN visibility TEXT NOT NULL DEFAULT 'unlisted' CHECK (visibility IN ('public', 'unlisted', 'private'));
+-- Anonymous pastes have nobody who could see them if they were private.
+ALTER TABLE pastr.pastes
+ADD CONSTRAINT pastes_private_has_owner CHECK (visibility <> 'private' OR owner IS NOT NULL);
+CREATE INDEX IF NOT EXISTS pastes_public_created_at_idx ON pastr.pastes(created_at)
+WHERE visibility = 'public';
@@ -0,0 +1,4 @@
+-- Confidence of an automatically detected language, between 0 and 1.
+-- NULL if the language was set explicitly or could not be detected.
+ALTER TABLE pastr.pastes
+ADD COLUMN language_confidence REAL CHECK (language_confidence BETWEEN 0 AND 1);
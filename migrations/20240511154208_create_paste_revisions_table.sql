@@ -0,0 +1,17 @@
+-- Every version of a paste. Editing a paste adds a revision, existing revisions are never modified.
+-- The pastes table keeps a copy of the latest revision together with its number.
+ALTER TABLE pastr.pastes
+ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+CREATE TABLE IF NOT EXISTS pastr.paste_revisions (
+    paste_id uuid NOT NULL REFERENCES pastr.pastes(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL CHECK (revision > 0),
+    title TEXT,
+    content TEXT NOT NULL,
+    language TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (paste_id, revision)
+);
+-- Existing pastes start out with their current content as the first revision.
+INSERT INTO pastr.paste_revisions (paste_id, revision, title, content, language, created_at)
+SELECT id, 1, title, content, language, created_at
+FROM pastr.pastes;
@@ -0,0 +1,5 @@
+-- Paste and revision a paste was forked from. Forks outlive the paste they were copied from.
+ALTER TABLE pastr.pastes
+ADD COLUMN forked_from uuid REFERENCES pastr.pastes(id) ON DELETE SET NULL,
+ADD COLUMN forked_from_revision INTEGER;
+CREATE INDEX IF NOT EXISTS pastes_forked_from_idx ON pastr.pastes(forked_from);
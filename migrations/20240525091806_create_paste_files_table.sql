@@ -0,0 +1,30 @@
+-- Files of every paste revision. A paste holds one or more files, each with its own language.
+-- Only files of pastes with more than one file are required to have a name.
+CREATE TABLE IF NOT EXISTS pastr.paste_files (
+    paste_id uuid NOT NULL,
+    revision INTEGER NOT NULL,
+    position INTEGER NOT NULL CHECK (position >= 0),
+    filename TEXT,
+    content TEXT NOT NULL,
+    language TEXT,
+    language_confidence REAL CHECK (language_confidence BETWEEN 0 AND 1),
+    PRIMARY KEY (paste_id, revision, position),
+    FOREIGN KEY (paste_id, revision)
+        REFERENCES pastr.paste_revisions(paste_id, revision) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX IF NOT EXISTS paste_files_filename_idx
+ON pastr.paste_files(paste_id, revision, filename);
+-- Existing revisions become a single unnamed file. Only the language of the latest
+-- revision is known to be detected, unless older revisions share it.
+INSERT INTO pastr.paste_files (paste_id, revision, position, content, language, language_confidence)
+SELECT r.paste_id, r.revision, 0, r.content, r.language,
+    CASE WHEN r.language IS NOT DISTINCT FROM p.language THEN p.language_confidence END
+FROM pastr.paste_revisions r
+JOIN pastr.pastes p ON p.id = r.paste_id;
+ALTER TABLE pastr.paste_revisions
+DROP COLUMN content,
+DROP COLUMN language;
+ALTER TABLE pastr.pastes
+DROP COLUMN content,
+DROP COLUMN language,
+DROP COLUMN language_confidence;
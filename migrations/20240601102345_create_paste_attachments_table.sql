@@ -0,0 +1,11 @@
+-- Binary files attached to a paste, like images or PDFs. Attachments are not part of the revisions of a paste.
+-- The media type is detected from the content when the attachment is stored.
+CREATE TABLE IF NOT EXISTS pastr.paste_attachments (
+    paste_id uuid NOT NULL REFERENCES pastr.pastes(id) ON DELETE CASCADE,
+    filename TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    size BIGINT NOT NULL CHECK (size > 0),
+    data BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (paste_id, filename)
+);
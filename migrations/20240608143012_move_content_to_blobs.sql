@@ -0,0 +1,21 @@
+-- Paste content moves out of the paste tables into a blob store. Rows only keep the key of their content.
+-- Blobs of the default Postgres store live in this table. Existing content is moved here,
+-- instances that switch to another store have to copy these blobs over, keeping their keys.
+CREATE TABLE IF NOT EXISTS pastr.blobs (
+    key TEXT PRIMARY KEY,
+    data BYTEA NOT NULL
+);
+
+ALTER TABLE pastr.paste_files ADD COLUMN blob_key TEXT;
+UPDATE pastr.paste_files SET blob_key = replace(gen_random_uuid()::text, '-', '');
+INSERT INTO pastr.blobs (key, data)
+SELECT blob_key, convert_to(content, 'UTF8') FROM pastr.paste_files;
+ALTER TABLE pastr.paste_files ALTER COLUMN blob_key SET NOT NULL;
+ALTER TABLE pastr.paste_files DROP COLUMN content;
+
+ALTER TABLE pastr.paste_attachments ADD COLUMN blob_key TEXT;
+UPDATE pastr.paste_attachments SET blob_key = replace(gen_random_uuid()::text, '-', '');
+INSERT INTO pastr.blobs (key, data)
+SELECT blob_key, data FROM pastr.paste_attachments;
+ALTER TABLE pastr.paste_attachments ALTER COLUMN blob_key SET NOT NULL;
+ALTER TABLE pastr.paste_attachments DROP COLUMN data;
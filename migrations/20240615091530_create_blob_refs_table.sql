@@ -0,0 +1,18 @@
+-- Count the rows referencing every blob, so blobs stored under the hash of their content
+-- can be shared and are only deleted once nothing references them anymore.
+CREATE TABLE pastr.blob_refs (
+    key TEXT PRIMARY KEY,
+    refs INTEGER NOT NULL CHECK (refs >= 0)
+);
+
+-- Blobs waiting to be collected
+CREATE INDEX blob_refs_unreferenced_idx ON pastr.blob_refs (key) WHERE refs = 0;
+
+-- Existing blobs keep their random keys, they just get counted
+INSERT INTO pastr.blob_refs (key, refs)
+SELECT blob_key, COUNT(*) FROM (
+    SELECT blob_key FROM pastr.paste_files
+    UNION ALL
+    SELECT blob_key FROM pastr.paste_attachments
+) refs
+GROUP BY blob_key;
@@ -0,0 +1,2 @@
+-- Codec the blob is compressed with. Blobs stored so far are uncompressed
+ALTER TABLE pastr.blob_refs ADD COLUMN codec TEXT NOT NULL DEFAULT 'none';
@@ -0,0 +1,2 @@
+-- Argon2 hash of the password that protects a paste. NULL for pastes everyone may view
+ALTER TABLE pastr.pastes ADD COLUMN password_hash TEXT;
@@ -0,0 +1,4 @@
+-- How the content of a paste is stored. Encrypted pastes only hold ciphertext that was produced by the client,
+-- the key to decrypt it never reaches the server.
+ALTER TABLE pastr.pastes
+ADD COLUMN kind TEXT NOT NULL DEFAULT 'plain' CHECK (kind IN ('plain', 'encrypted'));
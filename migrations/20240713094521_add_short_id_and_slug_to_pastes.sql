@@ -0,0 +1,12 @@
+-- Pastes are addressed by a short id in urls instead of their uuid.
+-- Existing pastes use their uuid as short id, so links to them keep working.
+ALTER TABLE pastr.pastes ADD COLUMN short_id TEXT;
+UPDATE pastr.pastes SET short_id = id::text;
+ALTER TABLE pastr.pastes ALTER COLUMN short_id SET NOT NULL;
+ALTER TABLE pastr.pastes ADD CONSTRAINT pastes_short_id_key UNIQUE (short_id);
+-- Vanity slug, which makes a paste reachable as /{username}/{slug}. Only pastes with an owner can claim one.
+ALTER TABLE pastr.pastes ADD COLUMN slug TEXT;
+ALTER TABLE pastr.pastes
+ADD CONSTRAINT pastes_slug_has_owner CHECK (slug IS NULL OR owner IS NOT NULL);
+CREATE UNIQUE INDEX IF NOT EXISTS pastes_owner_slug_idx ON pastr.pastes(owner, slug)
+WHERE slug IS NOT NULL;
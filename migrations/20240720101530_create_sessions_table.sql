@@ -0,0 +1,9 @@
+-- Login sessions of users. The session cookie holds a random token, only its SHA-256 hash is stored.
+-- Expired sessions are deleted by the background task that also deletes expired pastes.
+CREATE TABLE IF NOT EXISTS pastr.sessions (
+    token_hash TEXT PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES pastr.users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sessions_user_id_idx ON pastr.sessions (user_id);
@@ -0,0 +1,7 @@
+-- Signup requests are confirmed with a random token instead of the id of the user.
+-- Only the SHA-256 hash of the token is stored. Pending requests get a value no token hashes to,
+-- so their users have to request a new activation mail.
+ALTER TABLE pastr.users_confirmations ADD COLUMN IF NOT EXISTS token_hash TEXT;
+UPDATE pastr.users_confirmations SET token_hash = md5(random()::text || user_id::text) WHERE token_hash IS NULL;
+ALTER TABLE pastr.users_confirmations ALTER COLUMN token_hash SET NOT NULL;
+ALTER TABLE pastr.users_confirmations ADD CONSTRAINT users_confirmations_token_hash_key UNIQUE (token_hash);
@@ -0,0 +1,8 @@
+-- Pending password resets. The mailed link holds a random token, only its SHA-256 hash is stored.
+-- A user has at most one pending reset, requesting a new one replaces it.
+CREATE TABLE IF NOT EXISTS pastr.password_resets (
+    token_hash TEXT PRIMARY KEY,
+    user_id uuid NOT NULL UNIQUE REFERENCES pastr.users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
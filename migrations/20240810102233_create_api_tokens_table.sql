@@ -0,0 +1,15 @@
+-- Personal api tokens, which authenticate scripts as their user via `Authorization: Bearer`.
+-- Only the SHA-256 hash of a token is stored, it is shown to the user once when it is created.
+CREATE TABLE IF NOT EXISTS pastr.api_tokens (
+    id uuid PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES pastr.users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL CHECK (
+        cardinality(scopes) > 0
+        AND scopes <@ ARRAY['paste:read', 'paste:write', 'paste:delete']
+    ),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS api_tokens_user_id_idx ON pastr.api_tokens (user_id);
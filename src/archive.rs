@@ -0,0 +1,266 @@
+//! Bundling the files of a paste into a downloadable archive.
+//!
+//! Pastes are small and only consist of regular files, so both formats are written by hand
+//! instead of pulling in full archive libraries. All files are placed in a directory named `root`.
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use flate2::{write::DeflateEncoder, write::GzEncoder, Compression, Crc};
+use std::io::{self, Write};
+
+/// Size of a tar header and the unit tar content is padded to.
+const TAR_BLOCK: usize = 512;
+
+/// Bit of the zip flags that marks filenames as UTF-8.
+const ZIP_UTF8_FLAG: u16 = 1 << 11;
+
+/// Supported archive formats.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Return a string representation of the enum value, which is also the file extension.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+
+    /// Media type of archives in this format.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+        }
+    }
+
+    /// Bundle `files`, given as pairs of name and content, into an archive.
+    ///
+    /// * `root` - name of the directory that holds the files
+    /// * `modified` - modification time recorded for every file
+    pub fn write(
+        &self,
+        root: &str,
+        files: &[(String, &[u8])],
+        modified: DateTime<Utc>,
+    ) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Zip => zip(root, files, modified),
+            Self::TarGz => tar_gz(root, files, modified),
+        }
+    }
+}
+
+/// Write a gzip compressed ustar archive.
+fn tar_gz(root: &str, files: &[(String, &[u8])], modified: DateTime<Utc>) -> io::Result<Vec<u8>> {
+    let mut archive = GzEncoder::new(Vec::new(), Compression::default());
+
+    for (name, content) in files {
+        archive.write_all(&tar_header(root, name, content.len(), modified)?)?;
+        archive.write_all(content)?;
+        let padding = (TAR_BLOCK - content.len() % TAR_BLOCK) % TAR_BLOCK;
+        archive.write_all(&[0; TAR_BLOCK][..padding])?;
+    }
+    // an archive ends with two empty blocks
+    archive.write_all(&[0; TAR_BLOCK * 2])?;
+
+    archive.finish()
+}
+
+/// Build the ustar header of a regular file. The name of the directory goes into the prefix field.
+fn tar_header(
+    root: &str,
+    name: &str,
+    size: usize,
+    modified: DateTime<Utc>,
+) -> io::Result<[u8; TAR_BLOCK]> {
+    if name.len() > 100 || root.len() > 155 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "filename too long for a tar archive",
+        ));
+    }
+
+    let mut header = [0; TAR_BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    header[136..148].copy_from_slice(format!("{:011o}\0", modified.timestamp().max(0)).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + root.len()].copy_from_slice(root.as_bytes());
+
+    // the checksum is calculated with the checksum field itself filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    Ok(header)
+}
+
+/// Write a zip archive. Files are deflated, unless that does not make them any smaller.
+fn zip(root: &str, files: &[(String, &[u8])], modified: DateTime<Utc>) -> io::Result<Vec<u8>> {
+    let (time, date) = dos_date_time(modified);
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+
+    for (name, content) in files {
+        let path = format!("{root}/{name}");
+        let mut crc = Crc::new();
+        crc.update(content);
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content)?;
+        let deflated = encoder.finish()?;
+        let (method, data): (u16, &[u8]) = match deflated.len() < content.len() {
+            true => (8, &deflated),
+            false => (0, content),
+        };
+
+        let offset = zip_size(archive.len())?;
+        // fields shared by the local header and the central directory entry
+        let mut fields = Vec::with_capacity(26);
+        fields.extend_from_slice(&20u16.to_le_bytes());
+        fields.extend_from_slice(&ZIP_UTF8_FLAG.to_le_bytes());
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&time.to_le_bytes());
+        fields.extend_from_slice(&date.to_le_bytes());
+        fields.extend_from_slice(&crc.sum().to_le_bytes());
+        fields.extend_from_slice(&zip_size(data.len())?.to_le_bytes());
+        fields.extend_from_slice(&zip_size(content.len())?.to_le_bytes());
+        fields.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes());
+
+        archive.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        archive.extend_from_slice(&fields);
+        archive.extend_from_slice(path.as_bytes());
+        archive.extend_from_slice(data);
+
+        // version made by: unix, so the external attributes hold file permissions
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&(3u16 << 8 | 20).to_le_bytes());
+        directory.extend_from_slice(&fields);
+        // comment length, disk number and internal attributes
+        directory.extend_from_slice(&[0; 6]);
+        directory.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(path.as_bytes());
+    }
+
+    let entries = files.len() as u16;
+    let directory_offset = zip_size(archive.len())?;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    archive.extend_from_slice(&[0; 4]);
+    archive.extend_from_slice(&entries.to_le_bytes());
+    archive.extend_from_slice(&entries.to_le_bytes());
+    archive.extend_from_slice(&zip_size(directory.len())?.to_le_bytes());
+    archive.extend_from_slice(&directory_offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+
+    Ok(archive)
+}
+
+/// Convert a size or offset into a zip field. Archives beyond 4 GiB would need zip64.
+fn zip_size(size: usize) -> io::Result<u32> {
+    u32::try_from(size)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "archive too large for zip"))
+}
+
+/// Convert a point in time into the MS-DOS time and date used by zip, which start in 1980.
+fn dos_date_time(at: DateTime<Utc>) -> (u16, u16) {
+    if at.year() < 1980 {
+        return (0, 1 << 5 | 1);
+    }
+
+    let time = at.hour() << 11 | at.minute() << 5 | (at.second() / 2);
+    let date = (at.year() as u32 - 1980) << 9 | at.month() << 5 | at.day();
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn files() -> Vec<(String, &'static [u8])> {
+        vec![
+            (
+                "Cargo.toml".to_owned(),
+                b"[package]\nname = \"demo\"\n".as_slice(),
+            ),
+            ("main.rs".to_owned(), b"fn main() {}\n".as_slice()),
+        ]
+    }
+
+    #[test]
+    fn tar_contains_files_in_root_directory() {
+        let archive = ArchiveFormat::TarGz
+            .write("bundle", &files(), Utc::now())
+            .unwrap();
+        let mut tar = Vec::new();
+        GzDecoder::new(archive.as_slice())
+            .read_to_end(&mut tar)
+            .unwrap();
+
+        assert_eq!(tar.len() % TAR_BLOCK, 0);
+        assert!(tar.starts_with(b"Cargo.toml\0"));
+        assert!(tar[345..].starts_with(b"bundle\0"));
+        assert_eq!(&tar[TAR_BLOCK..TAR_BLOCK + 9], b"[package]");
+        assert!(tar[TAR_BLOCK * 2..].starts_with(b"main.rs\0"));
+        assert!(tar[tar.len() - TAR_BLOCK * 2..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn tar_header_checksum() {
+        let header = tar_header("bundle", "main.rs", 13, Utc::now()).unwrap();
+        let stored = u32::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8);
+        let sum: u32 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| match i {
+                148..=155 => u32::from(b' '),
+                _ => u32::from(b),
+            })
+            .sum();
+
+        assert_eq!(stored, Ok(sum));
+    }
+
+    #[test]
+    fn tar_rejects_long_names() {
+        assert!(tar_header("bundle", &"a".repeat(101), 0, Utc::now()).is_err());
+    }
+
+    #[test]
+    fn zip_lists_all_files() {
+        let archive = ArchiveFormat::Zip
+            .write("bundle", &files(), Utc::now())
+            .unwrap();
+        let end = &archive[archive.len() - 22..];
+
+        assert!(archive.starts_with(&0x04034b50u32.to_le_bytes()));
+        assert_eq!(&archive[30..47], b"bundle/Cargo.toml");
+        assert!(end.starts_with(&0x06054b50u32.to_le_bytes()));
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+    }
+
+    #[test]
+    fn dos_time_of_date() {
+        let at = Utc.with_ymd_and_hms(2024, 5, 25, 13, 37, 42).unwrap();
+
+        assert_eq!(
+            dos_date_time(at),
+            (13 << 11 | 37 << 5 | 21, 44 << 9 | 5 << 5 | 25)
+        );
+    }
+}
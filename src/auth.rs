@@ -1,6 +1,9 @@
 use anyhow::Context;
 use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString};
 use argon2::{Algorithm, Argon2, Params, PasswordVerifier, Version};
+use hmac::{Hmac, Mac};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, thiserror::Error)]
 pub enum AuthError {
@@ -10,6 +13,12 @@ pub enum AuthError {
     UnexpectedError(#[from] anyhow::Error),
 }
 
+/// PHC string of a hash with the parameters that [`hash_password`] uses, of a password nobody knows.
+///
+/// Logins of unknown users are verified against it, so they take as long as logins of existing users.
+pub const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$a5dISWaP4viqcpVTcR4xhg$cNcPJs5dV9ec/UxVKBshMbRZSJBgcglmR0bJ9xWu24A";
+
 /// Create an Argon2 instance that uses a specified `pepper` value for hashing and verification.
 fn argon2_with_pepper(pepper: &[u8]) -> Result<Argon2, AuthError> {
     match Argon2::new_with_secret(
@@ -64,6 +73,62 @@ pub fn verify_password_hash(password: &str, hash: &str, pepper: &[u8]) -> Result
         .map_err(AuthError::InvalidCredentials)
 }
 
+/// Generate a random token that can be handed out as a secret.
+///
+/// Tokens consist of 32 alphanumeric characters drawn from the operating system's random number generator.
+/// Only the hash computed by [`hash_token`] should be stored.
+pub fn generate_token() -> String {
+    Alphanumeric.sample_string(&mut OsRng, 32)
+}
+
+/// Compute the hex encoded SHA-256 hash of a token generated by [`generate_token`].
+///
+/// Tokens are random and long enough, so a fast hash without salt is sufficient for storing them.
+///
+/// * `token` - token to hash
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Compute the hex encoded HMAC-SHA256 of `message`, so it can be handed to clients and checked when it comes back.
+///
+/// * `message` - data to sign
+/// * `key` - secret key, e.g. the pepper set in [Config][c]
+///
+/// [c]: crate::config::Config
+pub fn sign(message: &[u8], key: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Check a signature computed by [`sign`]. The comparison runs in constant time.
+///
+/// * `message` - data the signature is supposed to be computed from
+/// * `signature` - hex encoded signature sent by a client
+/// * `key` - secret key that was used for signing
+pub fn verify_signature(message: &[u8], signature: &str, key: &[u8]) -> bool {
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Decode a hex string, returning `None` if it is not valid hex.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,4 +165,39 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn dummy_hash_costs_as_much_as_real_ones() {
+        let pepper = "long and bad pepper value - not use in production";
+
+        let hash =
+            hash_password("p4ssw0rd1", pepper.as_bytes()).expect("hashing of password failed");
+        let real = PasswordHash::new(&hash).unwrap();
+        let dummy = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+
+        assert_eq!(dummy.algorithm, real.algorithm);
+        assert_eq!(dummy.version, real.version);
+        assert_eq!(dummy.params, real.params);
+    }
+
+    #[test]
+    fn token_hash_is_stable() {
+        let token = generate_token();
+
+        assert_eq!(token.len(), 32);
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), hash_token(&generate_token()));
+    }
+
+    #[test]
+    fn signature_verifies() {
+        let key = b"long and bad pepper value - not use in production";
+        let signature = sign(b"unlock", key);
+
+        assert!(verify_signature(b"unlock", &signature, key));
+        assert!(!verify_signature(b"unlock!", &signature, key));
+        assert!(!verify_signature(b"unlock", &signature, b"other key"));
+        assert!(!verify_signature(b"unlock", "not hex", key));
+        assert!(!verify_signature(b"unlock", &signature[..10], key));
+    }
 }
@@ -1,13 +1,22 @@
+use crate::entity::Expiry;
+use crate::storage::Codec;
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use std::convert::TryFrom;
+use std::path::PathBuf;
 
 /// Contains general config for the application.
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct Config {
     pub app: AppConfig,
     pub database: DatabaseConfig,
+    /// Where the content of pastes is stored. Defaults to the database
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// How paste content is compressed before it is stored
+    #[serde(default)]
+    pub compression: CompressionConfig,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -17,6 +26,153 @@ pub struct AppConfig {
     pub base_url: String,
     pub pepper: Secret<String>,
     pub sendgrid_key: Secret<String>,
+    /// Maximum size of a paste in bytes
+    #[serde(
+        default = "default_max_paste_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_paste_size: usize,
+    /// Maximum size of a binary attachment in bytes
+    #[serde(
+        default = "default_max_attachment_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_attachment_size: usize,
+    /// Longest lifetime a paste may have. Pastes without an explicit lifetime get this one
+    #[serde(default = "default_max_expiry")]
+    pub max_expiry: Expiry,
+    /// Seconds between two runs of the task that deletes expired pastes
+    #[serde(
+        default = "default_reaper_interval",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub reaper_interval: u64,
+    /// Optional listener that accepts pastes over plain tcp connections
+    #[serde(default)]
+    pub tcp_listener: Option<TcpListenerConfig>,
+}
+
+fn default_max_paste_size() -> usize {
+    1024 * 1024
+}
+
+fn default_max_attachment_size() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_max_expiry() -> Expiry {
+    Expiry::Never
+}
+
+fn default_reaper_interval() -> u64 {
+    60
+}
+
+/// Config for the listener that accepts pastes over plain tcp, e.g. via `nc`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TcpListenerConfig {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    /// Seconds without new data after which a paste is considered complete
+    #[serde(
+        default = "default_tcp_idle_timeout",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub idle_timeout: u64,
+    /// Seconds a client may take to send a whole paste
+    #[serde(
+        default = "default_tcp_read_timeout",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub read_timeout: u64,
+    /// Connections that are handled at the same time, further connections are turned away
+    #[serde(
+        default = "default_tcp_max_connections",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_connections: usize,
+}
+
+fn default_tcp_idle_timeout() -> u64 {
+    2
+}
+
+fn default_tcp_read_timeout() -> u64 {
+    30
+}
+
+fn default_tcp_max_connections() -> usize {
+    64
+}
+
+/// Backend that stores the content of pastes, see [`BlobStore`](crate::storage::BlobStore).
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// Store content in the `pastr.blobs` table of the database
+    #[default]
+    Postgres,
+    /// Store content as files below `path`
+    Filesystem { path: PathBuf },
+    /// Store content in a bucket of an S3-compatible object storage
+    S3(S3Config),
+}
+
+/// Config for storing paste content in an S3-compatible object storage, like AWS S3 or MinIO.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct S3Config {
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// Url of the storage, e.g. `http://localhost:9000` for a local MinIO. `None` for AWS S3
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: Secret<String>,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_owned()
+}
+
+/// Config for compressing paste content before it is written to the storage.
+///
+/// Changing it only affects content stored afterwards, existing blobs keep their codec.
+#[derive(Debug, Copy, Clone, serde::Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default = "default_compression_codec")]
+    pub codec: Codec,
+    /// Content smaller than this many bytes is stored uncompressed
+    #[serde(
+        default = "default_compression_min_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub min_size: usize,
+}
+
+impl CompressionConfig {
+    /// Store all content uncompressed.
+    pub const NONE: Self = Self {
+        codec: Codec::None,
+        min_size: 0,
+    };
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            codec: default_compression_codec(),
+            min_size: default_compression_min_size(),
+        }
+    }
+}
+
+fn default_compression_codec() -> Codec {
+    Codec::Zstd
+}
+
+fn default_compression_min_size() -> usize {
+    4096
 }
 
 /// Config for the database connection.
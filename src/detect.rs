@@ -0,0 +1,514 @@
+use crate::highlight::is_known_language;
+use std::path::Path;
+
+/// Only the beginning of large pastes is inspected, which is plenty to tell languages apart.
+const MAX_DETECTION_SIZE: usize = 64 * 1024;
+
+/// Lines at the start and end of a paste that are searched for editor modelines.
+const MODELINE_LINES: usize = 5;
+
+/// Occurrences of a single feature beyond this do not add to the score,
+/// so one very common token can not outweigh everything else.
+const MAX_FEATURE_COUNT: usize = 5;
+
+/// Minimum score the best language needs to reach to be reported at all.
+const MIN_SCORE: f32 = 4.0;
+
+/// A language guessed for a paste.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedLanguage {
+    /// Name or file extension of the language, see [`is_known_language`]
+    pub language: String,
+    /// How certain the guess is, between 0 and 1
+    pub confidence: f32,
+}
+
+impl DetectedLanguage {
+    fn certain(language: String) -> Self {
+        Self {
+            language,
+            confidence: 1.0,
+        }
+    }
+}
+
+/// Guess the language of a paste that was created without one.
+///
+/// The extension of `filename` is used if it is known. Otherwise the content is checked for a shebang
+/// and for vim or emacs modelines, all of which are taken as certain. As a last resort the content is
+/// scored against token profiles of common languages. Returns `None` if nothing matches well enough.
+///
+/// * `filename` - filename of the paste, e.g. its title
+/// * `content` - content of the paste
+pub fn detect_language(filename: Option<&str>, content: &str) -> Option<DetectedLanguage> {
+    let content = truncate(content, MAX_DETECTION_SIZE);
+
+    filename
+        .and_then(|filename| Path::new(filename).extension())
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| is_known_language(extension))
+        .map(|extension| DetectedLanguage::certain(extension.to_lowercase()))
+        .or_else(|| shebang(content).map(DetectedLanguage::certain))
+        .or_else(|| modeline(content).map(DetectedLanguage::certain))
+        .or_else(|| classify(content))
+}
+
+/// Language of the interpreter named in the shebang line, e.g. `#!/usr/bin/env python3`.
+fn shebang(content: &str) -> Option<String> {
+    let line = content.lines().next()?.strip_prefix("#!")?;
+    let mut args = line.split_whitespace();
+    let interpreter = args.next()?.rsplit('/').next()?;
+
+    let interpreter = if interpreter == "env" {
+        // skip options like `env -S`
+        args.find(|arg| !arg.starts_with('-'))?
+    } else {
+        interpreter
+    };
+    normalize(interpreter)
+}
+
+/// Language set by a vim (`vim: set ft=rust:`) or emacs (`-*- mode: python -*-`) modeline.
+fn modeline(content: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let tail = lines
+        .len()
+        .saturating_sub(MODELINE_LINES)
+        .max(MODELINE_LINES);
+
+    lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(tail))
+        .find_map(|line| vim_modeline(line).or_else(|| emacs_modeline(line)))
+}
+
+fn vim_modeline(line: &str) -> Option<String> {
+    let (_, settings) = ["vim:", "vi:", "ex:"]
+        .iter()
+        .find_map(|marker| line.split_once(marker))?;
+
+    settings
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|setting| {
+            let (key, value) = setting.split_once('=')?;
+            matches!(key, "ft" | "filetype" | "syn" | "syntax").then_some(value)
+        })
+        .and_then(normalize)
+}
+
+fn emacs_modeline(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+
+    let mode = if variables.contains(':') {
+        variables.split(';').find_map(|variable| {
+            let (key, value) = variable.split_once(':')?;
+            (key.trim().eq_ignore_ascii_case("mode")).then_some(value)
+        })?
+    } else {
+        variables
+    };
+    normalize(mode.trim())
+}
+
+/// Map names of interpreters, file types and editor modes to a language that can be highlighted.
+fn normalize(name: &str) -> Option<String> {
+    let name = name
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .to_lowercase();
+
+    let language = match name.as_str() {
+        "sh" | "bash" | "zsh" | "ksh" | "dash" | "ash" => "bash",
+        "node" | "nodejs" | "deno" | "bun" | "js" => "javascript",
+        "python" | "pypy" => "python",
+        "cpp" => "c++",
+        other => other,
+    };
+    is_known_language(language).then(|| language.to_owned())
+}
+
+/// A token that hints at a language, together with its weight.
+enum Feature {
+    /// Occurs anywhere in the content
+    Contains(&'static str, f32),
+    /// Occurs at the start of a line, ignoring indentation
+    LineStart(&'static str, f32),
+}
+
+use Feature::{Contains, LineStart};
+
+/// Tokens that are typical for a language.
+struct Profile {
+    language: &'static str,
+    features: &'static [Feature],
+}
+
+const PROFILES: &[Profile] = &[
+    Profile {
+        language: "rust",
+        features: &[
+            LineStart("fn ", 2.0),
+            Contains("pub fn ", 3.0),
+            Contains("let mut ", 3.0),
+            Contains("impl ", 2.0),
+            Contains("&self", 3.0),
+            Contains("&mut ", 2.0),
+            Contains("println!(", 3.0),
+            Contains("#[derive(", 3.0),
+            Contains(".unwrap()", 3.0),
+            Contains("Option<", 2.0),
+            Contains("Vec<", 2.0),
+            Contains("::", 1.0),
+            Contains("->", 1.0),
+        ],
+    },
+    Profile {
+        language: "python",
+        features: &[
+            LineStart("def ", 3.0),
+            LineStart("import ", 2.0),
+            LineStart("from ", 2.0),
+            LineStart("elif ", 3.0),
+            LineStart("class ", 1.0),
+            Contains("self.", 2.0),
+            Contains("__init__", 3.0),
+            Contains("if __name__", 3.0),
+            Contains(" in range(", 3.0),
+            Contains("):\n", 2.0),
+            Contains("print(", 1.0),
+            Contains("None", 1.0),
+            Contains("True", 1.0),
+            Contains("False", 1.0),
+        ],
+    },
+    Profile {
+        language: "javascript",
+        features: &[
+            LineStart("const ", 2.0),
+            LineStart("let ", 1.0),
+            LineStart("export default", 3.0),
+            Contains("function ", 2.0),
+            Contains("=> ", 2.0),
+            Contains("console.log(", 3.0),
+            Contains("===", 3.0),
+            Contains("require(", 2.0),
+            Contains("document.", 3.0),
+            Contains("undefined", 2.0),
+            Contains("});", 2.0),
+        ],
+    },
+    Profile {
+        language: "go",
+        features: &[
+            LineStart("package ", 3.0),
+            LineStart("func ", 3.0),
+            LineStart("import (", 3.0),
+            Contains(":=", 2.0),
+            Contains("fmt.", 3.0),
+            Contains("err != nil", 3.0),
+            Contains("go func", 3.0),
+            Contains("chan ", 2.0),
+        ],
+    },
+    Profile {
+        language: "c",
+        features: &[
+            LineStart("#include <", 2.0),
+            LineStart("#define ", 2.0),
+            Contains("int main(", 2.0),
+            Contains("printf(", 2.0),
+            Contains("malloc(", 3.0),
+            Contains("sizeof(", 2.0),
+            Contains("NULL", 2.0),
+            Contains("void ", 1.0),
+            Contains("struct ", 1.0),
+            Contains("->", 1.0),
+        ],
+    },
+    Profile {
+        language: "c++",
+        features: &[
+            LineStart("#include <", 2.0),
+            Contains("#include <iostream>", 3.0),
+            Contains("std::", 3.0),
+            Contains("cout <<", 3.0),
+            Contains("template <", 3.0),
+            Contains("template<", 3.0),
+            Contains("nullptr", 3.0),
+            Contains("namespace ", 2.0),
+            Contains("public:", 2.0),
+            Contains("::", 1.0),
+        ],
+    },
+    Profile {
+        language: "java",
+        features: &[
+            LineStart("import java", 3.0),
+            LineStart("@Override", 3.0),
+            LineStart("package ", 1.0),
+            Contains("public class ", 3.0),
+            Contains("public static void main", 3.0),
+            Contains("System.out.", 3.0),
+            Contains("String[]", 2.0),
+            Contains("private ", 1.0),
+            Contains("new ", 1.0),
+        ],
+    },
+    Profile {
+        language: "bash",
+        features: &[
+            LineStart("if [", 3.0),
+            LineStart("echo ", 2.0),
+            LineStart("export ", 2.0),
+            LineStart("sudo ", 2.0),
+            LineStart("fi", 2.0),
+            LineStart("done", 2.0),
+            LineStart("cd ", 1.0),
+            Contains("; then", 3.0),
+            Contains("; do", 3.0),
+            Contains("$(", 2.0),
+            Contains("${", 1.0),
+        ],
+    },
+    Profile {
+        language: "sql",
+        features: &[
+            Contains("SELECT ", 3.0),
+            Contains("INSERT INTO", 3.0),
+            Contains("CREATE TABLE", 3.0),
+            Contains("ALTER TABLE", 3.0),
+            Contains("FROM ", 2.0),
+            Contains("WHERE ", 2.0),
+            Contains("JOIN ", 2.0),
+            Contains("VARCHAR", 2.0),
+            Contains("select ", 1.0),
+            Contains(" from ", 1.0),
+        ],
+    },
+    Profile {
+        language: "html",
+        features: &[
+            Contains("<!DOCTYPE html", 5.0),
+            Contains("<!doctype html", 5.0),
+            Contains("<html", 3.0),
+            Contains("<body", 3.0),
+            Contains("<div", 2.0),
+            Contains("<script", 2.0),
+            Contains("href=\"", 2.0),
+            Contains("class=\"", 1.0),
+            Contains("</", 1.0),
+        ],
+    },
+    Profile {
+        language: "json",
+        features: &[
+            LineStart("{", 1.0),
+            LineStart("\"", 1.0),
+            Contains("\": ", 2.0),
+            Contains("\": {", 2.0),
+            Contains("\": [", 2.0),
+            Contains("},", 1.0),
+        ],
+    },
+    Profile {
+        language: "yaml",
+        features: &[
+            LineStart("---", 2.0),
+            LineStart("apiVersion:", 3.0),
+            LineStart("version:", 2.0),
+            LineStart("- name:", 3.0),
+            LineStart("name:", 1.0),
+            LineStart("- ", 1.0),
+        ],
+    },
+    Profile {
+        language: "ruby",
+        features: &[
+            LineStart("puts ", 3.0),
+            LineStart("require '", 3.0),
+            LineStart("attr_accessor", 3.0),
+            LineStart("module ", 2.0),
+            LineStart("def ", 1.0),
+            LineStart("end", 1.0),
+            Contains(".each do", 3.0),
+            Contains(" do |", 3.0),
+        ],
+    },
+    Profile {
+        language: "php",
+        features: &[
+            Contains("<?php", 10.0),
+            Contains("$this->", 3.0),
+            LineStart("echo ", 1.0),
+        ],
+    },
+    Profile {
+        language: "diff",
+        features: &[
+            LineStart("diff --git", 5.0),
+            LineStart("@@ ", 3.0),
+            LineStart("+++ ", 3.0),
+            LineStart("--- ", 2.0),
+            LineStart("index ", 1.0),
+        ],
+    },
+    Profile {
+        language: "markdown",
+        features: &[
+            LineStart("```", 3.0),
+            LineStart("## ", 2.0),
+            LineStart("# ", 1.0),
+            LineStart("* ", 1.0),
+            LineStart("> ", 1.0),
+            Contains("](", 2.0),
+            Contains("**", 1.0),
+        ],
+    },
+    Profile {
+        language: "css",
+        features: &[
+            LineStart("@media", 3.0),
+            Contains("px;", 3.0),
+            Contains("color:", 2.0),
+            Contains("margin:", 2.0),
+            Contains("padding:", 2.0),
+            Contains("display:", 2.0),
+            Contains("font-", 1.0),
+        ],
+    },
+];
+
+/// Score `content` against every [`Profile`] and pick the best match.
+///
+/// The confidence is the share of the best score among all scores,
+/// scaled down for pastes that only contain a few hints.
+fn classify(content: &str) -> Option<DetectedLanguage> {
+    let lines: Vec<&str> = content.lines().map(str::trim_start).collect();
+
+    let scores: Vec<(&str, f32)> = PROFILES
+        .iter()
+        .map(|profile| {
+            let score = profile
+                .features
+                .iter()
+                .map(|feature| {
+                    let (count, weight) = match feature {
+                        Contains(token, weight) => (content.matches(token).count(), weight),
+                        LineStart(token, weight) => (
+                            lines.iter().filter(|line| line.starts_with(token)).count(),
+                            weight,
+                        ),
+                    };
+                    count.min(MAX_FEATURE_COUNT) as f32 * weight
+                })
+                .sum();
+            (profile.language, score)
+        })
+        .collect();
+
+    let total: f32 = scores.iter().map(|(_, score)| score).sum();
+    let (language, best) = scores.into_iter().max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    if best < MIN_SCORE {
+        return None;
+    }
+
+    let confidence = best / total * (1.0 - (-best / 10.0).exp());
+    Some(DetectedLanguage {
+        language: language.to_owned(),
+        confidence: (confidence * 100.0).round() / 100.0,
+    })
+}
+
+/// Cut `content` to at most `max` bytes, without splitting a character.
+fn truncate(content: &str, max: usize) -> &str {
+    if content.len() <= max {
+        return content;
+    }
+    let mut end = max;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    &content[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(filename: Option<&str>, content: &str) -> Option<String> {
+        detect_language(filename, content).map(|detected| detected.language)
+    }
+
+    #[test]
+    fn profiles_can_be_highlighted() {
+        for profile in PROFILES {
+            assert!(
+                is_known_language(profile.language),
+                "{} can not be highlighted",
+                profile.language
+            );
+        }
+    }
+
+    #[test]
+    fn language_from_filename() {
+        let detected = detect_language(Some("build.rs"), "whatever").unwrap();
+
+        assert_eq!(detected.language, "rs");
+        assert_eq!(detected.confidence, 1.0);
+    }
+
+    #[test]
+    fn language_from_shebang() {
+        assert_eq!(detected(None, "#!/bin/sh\nls\n").as_deref(), Some("bash"));
+        assert_eq!(
+            detected(None, "#!/usr/bin/env -S python3 -u\n").as_deref(),
+            Some("python")
+        );
+        assert_eq!(detected(None, "#!/usr/bin/unknown\n"), None);
+    }
+
+    #[test]
+    fn language_from_modeline() {
+        assert_eq!(
+            detected(None, "some text\n# vim: set ft=ruby:\n").as_deref(),
+            Some("ruby")
+        );
+        assert_eq!(
+            detected(None, "// -*- mode: c++; indent-tabs-mode: nil -*-\n").as_deref(),
+            Some("c++")
+        );
+        assert_eq!(
+            detected(None, "-*- python -*-\n").as_deref(),
+            Some("python")
+        );
+    }
+
+    #[test]
+    fn language_from_content() {
+        let rust =
+            "fn main() {\n    let mut v: Vec<u8> = Vec::new();\n    println!(\"{:?}\", v);\n}\n";
+        let python = "import os\n\ndef main():\n    for i in range(3):\n        print(i)\n";
+        let sql = "SELECT id, name FROM users WHERE active JOIN teams;\n";
+
+        assert_eq!(detected(None, rust).as_deref(), Some("rust"));
+        assert_eq!(detected(None, python).as_deref(), Some("python"));
+        assert_eq!(detected(None, sql).as_deref(), Some("sql"));
+    }
+
+    #[test]
+    fn prose_is_not_detected() {
+        assert_eq!(
+            detected(None, "Hello, these are notes from the meeting.\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn truncate_on_char_boundary() {
+        assert_eq!(truncate("äöü", 3), "ä");
+        assert_eq!(truncate("abc", 5), "abc");
+    }
+}
@@ -0,0 +1,388 @@
+/// Edit distance above which the diff gives up on finding a minimal edit script
+/// and replaces the changed region as a whole, which keeps time and memory bounded.
+const MAX_EDIT_DISTANCE: usize = 1024;
+
+/// How a line changed between two texts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    Equal,
+    Delete,
+    Insert,
+}
+
+impl Change {
+    /// Css class used to render lines with this change.
+    pub fn class(&self) -> &str {
+        match self {
+            Self::Equal => "diff-equal",
+            Self::Delete => "diff-delete",
+            Self::Insert => "diff-insert",
+        }
+    }
+
+    /// Marker in front of lines with this change in unified diffs.
+    pub fn marker(&self) -> &str {
+        match self {
+            Self::Equal => " ",
+            Self::Delete => "-",
+            Self::Insert => "+",
+        }
+    }
+}
+
+/// A single line of a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine<'a> {
+    pub change: Change,
+    /// Line number in the old text, `None` for inserted lines
+    pub old_number: Option<usize>,
+    /// Line number in the new text, `None` for deleted lines
+    pub new_number: Option<usize>,
+    pub text: &'a str,
+}
+
+/// Row of a side-by-side diff, see [`Hunk::split_rows`].
+#[derive(Debug, Clone)]
+pub struct SplitRow<'a, 'b> {
+    pub old: Option<&'b DiffLine<'a>>,
+    pub new: Option<&'b DiffLine<'a>>,
+}
+
+/// Group of changed lines together with the unchanged lines around them.
+#[derive(Debug, Clone)]
+pub struct Hunk<'a> {
+    /// Number of the first old line, or of the line in front of the hunk if it contains no old lines
+    pub old_start: usize,
+    pub old_len: usize,
+    /// Number of the first new line, or of the line in front of the hunk if it contains no new lines
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine<'a>>,
+}
+
+impl<'a> Hunk<'a> {
+    /// Header of the hunk as used by unified diffs, e.g. `@@ -1,4 +1,5 @@`.
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+
+    /// Arrange the lines of the hunk side by side.
+    ///
+    /// Unchanged lines appear on both sides. Deleted lines are paired with the lines inserted in their place.
+    pub fn split_rows(&self) -> Vec<SplitRow<'a, '_>> {
+        let mut rows = Vec::new();
+        let mut deleted = Vec::new();
+        let mut inserted = Vec::new();
+
+        for line in self.lines.iter().chain(std::iter::once(&END)) {
+            match line.change {
+                Change::Delete => deleted.push(line),
+                Change::Insert => inserted.push(line),
+                Change::Equal => {
+                    let changed = deleted.len().max(inserted.len());
+                    for i in 0..changed {
+                        rows.push(SplitRow {
+                            old: deleted.get(i).copied(),
+                            new: inserted.get(i).copied(),
+                        });
+                    }
+                    deleted.clear();
+                    inserted.clear();
+
+                    if !std::ptr::eq(line, &END) {
+                        rows.push(SplitRow {
+                            old: Some(line),
+                            new: Some(line),
+                        });
+                    }
+                }
+            }
+        }
+        rows
+    }
+}
+
+/// Sentinel that flushes pending changes in [`Hunk::split_rows`].
+static END: DiffLine<'static> = DiffLine {
+    change: Change::Equal,
+    old_number: None,
+    new_number: None,
+    text: "",
+};
+
+/// Compute the line based diff between `old` and `new`.
+///
+/// Returns the hunks of changed lines, each surrounded by up to `context` unchanged lines.
+/// Hunks whose context would overlap are merged. Identical texts produce no hunks.
+///
+/// * `old` - text before the change
+/// * `new` - text after the change
+/// * `context` - number of unchanged lines to show around changes
+pub fn diff_lines<'a>(old: &'a str, new: &'a str, context: usize) -> Vec<Hunk<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    let (mut old_number, mut new_number) = (0, 0);
+    for change in edit_script(&old, &new) {
+        let text = match change {
+            Change::Equal | Change::Delete => old[old_number],
+            Change::Insert => new[new_number],
+        };
+        let line = DiffLine {
+            change,
+            old_number: (change != Change::Insert).then_some(old_number + 1),
+            new_number: (change != Change::Delete).then_some(new_number + 1),
+            text,
+        };
+        if change != Change::Insert {
+            old_number += 1;
+        }
+        if change != Change::Delete {
+            new_number += 1;
+        }
+        lines.push(line);
+    }
+
+    hunks(lines, context)
+}
+
+/// Cut the full list of lines into hunks, dropping unchanged lines that are too far away from any change.
+fn hunks(lines: Vec<DiffLine<'_>>, context: usize) -> Vec<Hunk<'_>> {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.change != Change::Equal)
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let count = |lines: &[DiffLine], side: fn(&DiffLine) -> Option<usize>| {
+                lines.iter().filter(|line| side(line).is_some()).count()
+            };
+            let old_before = count(&lines[..start], |line| line.old_number);
+            let new_before = count(&lines[..start], |line| line.new_number);
+            let old_len = count(&lines[start..end], |line| line.old_number);
+            let new_len = count(&lines[start..end], |line| line.new_number);
+
+            Hunk {
+                old_start: old_before + usize::from(old_len > 0),
+                old_len,
+                new_start: new_before + usize::from(new_len > 0),
+                new_len,
+                lines: lines[start..end].to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Shortest sequence of changes that turns `old` into `new`, using the algorithm by Eugene W. Myers.
+///
+/// Common lines at the start and end are skipped before searching. If the remaining texts differ by more
+/// than [`MAX_EDIT_DISTANCE`] lines, all of their lines are reported as deleted and inserted instead.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let middle = myers(a, b).unwrap_or_else(|| {
+        let mut changes = vec![Change::Delete; a.len()];
+        changes.extend(std::iter::repeat_n(Change::Insert, b.len()));
+        changes
+    });
+
+    let mut changes = vec![Change::Equal; prefix];
+    changes.extend(middle);
+    changes.extend(std::iter::repeat_n(Change::Equal, suffix));
+    changes
+}
+
+/// Greedy forward search of the Myers algorithm followed by a backtrack through the recorded steps.
+///
+/// Returns `None` if the edit distance exceeds [`MAX_EDIT_DISTANCE`].
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<Change>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // furthest reaching x per diagonal `k` after each step `d`, only for `k` in `-d..=d`
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max.min(MAX_EDIT_DISTANCE) as isize {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d
+                || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize])
+            {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                break 'search;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+
+    let last = trace.last()?;
+    let d = trace.len() as isize - 1;
+    let end_k = n - m;
+    if end_k.abs() > d || last[(end_k + d) as usize] < n {
+        // gave up before reaching the end of both texts
+        return None;
+    }
+
+    let x_at = |d: isize, k: isize| trace[d as usize][(k + d) as usize];
+    let mut changes = Vec::with_capacity((n + m) as usize);
+    let (mut x, mut y) = (n, m);
+
+    for d in (1..=d).rev() {
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && x_at(d - 1, k - 1) < x_at(d - 1, k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = x_at(d - 1, prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            changes.push(Change::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        changes.push(if prev_k == k + 1 {
+            Change::Insert
+        } else {
+            Change::Delete
+        });
+        x = prev_x;
+        y = prev_y;
+    }
+    changes.extend(std::iter::repeat_n(Change::Equal, x as usize));
+
+    changes.reverse();
+    Some(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unified(old: &str, new: &str) -> String {
+        diff_lines(old, new, 1)
+            .iter()
+            .flat_map(|hunk| {
+                std::iter::once(hunk.header()).chain(
+                    hunk.lines
+                        .iter()
+                        .map(|line| format!("{}{}", line.change.marker(), line.text)),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn identical_texts_have_no_hunks() {
+        assert!(diff_lines("a\nb\n", "a\nb\n", 3).is_empty());
+    }
+
+    #[test]
+    fn changed_line() {
+        assert_eq!(
+            unified("a\nb\nc\nd\n", "a\nb\nx\nd\n"),
+            "@@ -2,3 +2,3 @@\n b\n-c\n+x\n d"
+        );
+    }
+
+    #[test]
+    fn insertion_into_empty_text() {
+        assert_eq!(unified("", "a\nb\n"), "@@ -0,0 +1,2 @@\n+a\n+b");
+    }
+
+    #[test]
+    fn insertion_without_context() {
+        let hunks = diff_lines("a\nb\n", "a\nx\nb\n", 0);
+
+        assert_eq!(hunks[0].header(), "@@ -1,0 +2,1 @@");
+    }
+
+    #[test]
+    fn distant_changes_are_separate_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n";
+        let new = "x\n2\n3\n4\n5\n6\ny\n";
+
+        assert_eq!(diff_lines(old, new, 1).len(), 2);
+        assert_eq!(diff_lines(old, new, 3).len(), 1);
+    }
+
+    #[test]
+    fn edit_script_is_minimal() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let changes = edit_script(&old, &new);
+
+        assert_eq!(changes.iter().filter(|c| **c != Change::Equal).count(), 5);
+    }
+
+    #[test]
+    fn large_changes_fall_back_to_replacement() {
+        let old: Vec<String> = (0..=MAX_EDIT_DISTANCE).map(|i| format!("a{}", i)).collect();
+        let new: Vec<String> = (0..=MAX_EDIT_DISTANCE).map(|i| format!("b{}", i)).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+        assert_eq!(myers(&old, &new), None);
+        assert_eq!(edit_script(&old, &new).len(), old.len() + new.len());
+    }
+
+    #[test]
+    fn split_rows_pair_deletions_with_insertions() {
+        let hunks = diff_lines("a\nb\nc\n", "a\nx\ny\nc\n", 1);
+        let rows = hunks[0].split_rows();
+        let texts: Vec<(Option<&str>, Option<&str>)> = rows
+            .iter()
+            .map(|row| (row.old.map(|l| l.text), row.new.map(|l| l.text)))
+            .collect();
+
+        assert_eq!(
+            texts,
+            vec![
+                (Some("a"), Some("a")),
+                (Some("b"), Some("x")),
+                (None, Some("y")),
+                (Some("c"), Some("c")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use super::blob::{self, StoredBlob};
+use super::file::is_valid_filename;
+use super::{Editor, Paste, PasteError};
+use crate::sniff::media_type;
+use crate::storage::{content_key, BlobStore};
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, PgConnection, PgPool, Row};
+use uuid::Uuid;
+
+/// Values used to attach a binary file to a [`Paste`](super::Paste).
+#[derive(Debug, Clone)]
+pub struct NewAttachment<'a> {
+    pub filename: &'a str,
+    pub data: &'a [u8],
+}
+
+/// A binary file attached to a [`Paste`](super::Paste), like an image or a PDF.
+///
+/// Only holds the metadata, the content is loaded from the [`BlobStore`] via [`Attachment::fetch_data`].
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub paste_id: Uuid,
+    /// Unique within the paste
+    pub filename: String,
+    /// Media type detected from the content. Never taken from the client
+    pub mime_type: String,
+    /// Size of the content in bytes
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Columns that are selected to build an [`Attachment`] via [`Attachment::from_row`].
+const ATTACHMENT_COLUMNS: &str = "paste_id, filename, mime_type, size, created_at";
+
+impl Attachment {
+    /// List the attachments of a paste, ordered by name.
+    ///
+    /// Does not check whether the paste may be viewed, fetch the paste itself for that.
+    ///
+    /// * `paste_id` - UUID of the paste
+    /// * `pool` - Connection pool to use for the query
+    pub async fn list(paste_id: &Uuid, pool: &PgPool) -> Result<Vec<Self>, anyhow::Error> {
+        let rows = sqlx::query(&format!(
+            "
+            SELECT {ATTACHMENT_COLUMNS}
+            FROM pastr.paste_attachments
+            WHERE paste_id = $1
+            ORDER BY filename;
+            "
+        ))
+        .bind(paste_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(Self::from_row)
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Fetch an attachment together with its content.
+    ///
+    /// Does not check whether the paste may be viewed, fetch the paste itself for that.
+    /// Returns [`PasteError::AttachmentNotFound`] if there is no such attachment.
+    ///
+    /// * `paste_id` - UUID of the paste
+    /// * `filename` - name of the attachment
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content
+    pub async fn fetch_data(
+        paste_id: &Uuid,
+        filename: &str,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<(Self, Vec<u8>), anyhow::Error> {
+        let row = sqlx::query(&format!(
+            "
+            SELECT {ATTACHMENT_COLUMNS}, blob_key, codec
+            FROM pastr.paste_attachments
+            JOIN pastr.blob_refs ON key = blob_key
+            WHERE paste_id = $1 AND filename = $2;
+            "
+        ))
+        .bind(paste_id)
+        .bind(filename)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Err(anyhow::anyhow!(PasteError::AttachmentNotFound));
+        };
+        let blob = StoredBlob::from_row(&row)?;
+        match blob.read(store).await? {
+            Some(data) => Ok((Self::from_row(&row)?, data)),
+            None => Err(anyhow::anyhow!(
+                "blob {} of an attachment is missing",
+                blob.key
+            )),
+        }
+    }
+
+    /// Attach a binary file to an existing paste.
+    ///
+    /// Returns [`PasteError::PasteNotFound`] if there is no such paste, [`PasteError::InvalidEditToken`] if
+    /// `editor` may not change it and [`PasteError::InvalidAttachment`] if the attachment is empty, its name is
+    /// invalid or taken, or the paste is burn-after-reading or encrypted.
+    ///
+    /// * `short_id` - short id of the paste
+    /// * `editor` - edit token or owner that proves the change is allowed
+    /// * `attachment` - name and content of the attachment
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] to store the content in
+    pub async fn add(
+        short_id: &str,
+        editor: Editor<'_>,
+        attachment: &NewAttachment<'_>,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<Self, anyhow::Error> {
+        let blob_key = content_key(attachment.data);
+        blob::reserve([blob_key.as_str()], pool).await?;
+
+        let mut tx = pool.begin().await?;
+        let paste_id = &Paste::lock_for_edit(short_id, editor, &mut tx).await?;
+
+        let accepts_attachments: bool = sqlx::query(
+            "SELECT NOT burn_after_reading AND kind = 'plain' AS accepts FROM pastr.pastes WHERE id = $1;",
+        )
+        .bind(paste_id)
+        .fetch_one(&mut *tx)
+        .await?
+        .try_get("accepts")?;
+
+        if !accepts_attachments {
+            return Err(anyhow::anyhow!(PasteError::InvalidAttachment));
+        }
+
+        validate_attachments(std::slice::from_ref(attachment))?;
+        let taken = sqlx::query(
+            "SELECT 1 FROM pastr.paste_attachments WHERE paste_id = $1 AND filename = $2;",
+        )
+        .bind(paste_id)
+        .bind(attachment.filename)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if taken.is_some() {
+            return Err(anyhow::anyhow!(PasteError::InvalidAttachment));
+        }
+
+        blob::acquire(&mut tx, store, [(blob_key.as_str(), attachment.data)]).await?;
+        let row = sqlx::query(&format!(
+            "
+            INSERT INTO pastr.paste_attachments (paste_id, filename, mime_type, size, blob_key)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {ATTACHMENT_COLUMNS};
+            "
+        ))
+        .bind(paste_id)
+        .bind(attachment.filename)
+        .bind(media_type(attachment.data))
+        .bind(attachment.data.len() as i64)
+        .bind(&blob_key)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Self::from_row(&row)?)
+    }
+
+    /// Remove an attachment from a paste.
+    ///
+    /// Returns [`PasteError::PasteNotFound`] if there is no such paste, [`PasteError::InvalidEditToken`] if
+    /// `editor` may not change it and [`PasteError::AttachmentNotFound`] if there was no attachment to remove.
+    ///
+    /// * `short_id` - short id of the paste
+    /// * `editor` - edit token or owner that proves the change is allowed
+    /// * `filename` - name of the attachment
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content
+    pub async fn delete(
+        short_id: &str,
+        editor: Editor<'_>,
+        filename: &str,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+        let paste_id = Paste::lock_for_edit(short_id, editor, &mut tx).await?;
+        let row = sqlx::query(
+            "
+            DELETE FROM pastr.paste_attachments WHERE paste_id = $1 AND filename = $2
+            RETURNING blob_key;
+            ",
+        )
+        .bind(paste_id)
+        .bind(filename)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Err(anyhow::anyhow!(PasteError::AttachmentNotFound));
+        };
+        blob::release_key(row.try_get("blob_key")?, &mut tx).await?;
+        tx.commit().await?;
+
+        blob::collect_released(pool, store).await;
+        Ok(())
+    }
+
+    /// Store attachments, as part of the transaction that creates or changes the paste.
+    ///
+    /// The media type of every attachment is detected from its content, which is stored under its hash
+    /// in `store` unless an identical blob exists already.
+    pub(super) async fn record(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        paste_id: &Uuid,
+        attachments: &[NewAttachment<'_>],
+    ) -> Result<(), anyhow::Error> {
+        let blob_keys: Vec<String> = attachments
+            .iter()
+            .map(|attachment| content_key(attachment.data))
+            .collect();
+        blob::acquire(
+            &mut *conn,
+            store,
+            attachments
+                .iter()
+                .zip(&blob_keys)
+                .map(|(attachment, blob_key)| (blob_key.as_str(), attachment.data)),
+        )
+        .await?;
+
+        for (attachment, blob_key) in attachments.iter().zip(&blob_keys) {
+            sqlx::query(
+                "INSERT INTO pastr.paste_attachments (paste_id, filename, mime_type, size, blob_key)
+                VALUES ($1, $2, $3, $4, $5);",
+            )
+            .bind(paste_id)
+            .bind(attachment.filename)
+            .bind(media_type(attachment.data))
+            .bind(attachment.data.len() as i64)
+            .bind(blob_key)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy all attachments of a paste to another one, as part of the transaction that forks the paste.
+    ///
+    /// The copies share the blobs of the originals, each of them holding a reference of its own.
+    pub(super) async fn copy(
+        conn: &mut PgConnection,
+        from: &Uuid,
+        to: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        // lock the originals, so their blobs stay referenced until the copies are committed
+        let blob_keys = sqlx::query(
+            "
+            WITH originals AS (
+                SELECT filename, mime_type, size, blob_key FROM pastr.paste_attachments
+                WHERE paste_id = $1
+                FOR SHARE
+            )
+            INSERT INTO pastr.paste_attachments (paste_id, filename, mime_type, size, blob_key)
+            SELECT $2, filename, mime_type, size, blob_key FROM originals
+            RETURNING blob_key;
+            ",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.try_get("blob_key"))
+        .collect::<Result<Vec<String>, sqlx::Error>>()?;
+
+        blob::share(&blob_keys, conn).await
+    }
+
+    /// Build an [`Attachment`] from a row of the `pastr.paste_attachments` table.
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            paste_id: row.try_get("paste_id")?,
+            filename: row.try_get("filename")?,
+            mime_type: row.try_get("mime_type")?,
+            size: row.try_get("size")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Check that the given attachments can be stored together on one paste.
+///
+/// Every attachment needs content and a distinct name without path separators or control characters.
+/// Returns [`PasteError::InvalidAttachment`] otherwise.
+pub(super) fn validate_attachments(attachments: &[NewAttachment]) -> Result<(), PasteError> {
+    let mut seen = Vec::with_capacity(attachments.len());
+    let valid = attachments.iter().all(|attachment| {
+        let valid = !attachment.data.is_empty()
+            && is_valid_filename(attachment.filename)
+            && !seen.contains(&attachment.filename);
+        seen.push(attachment.filename);
+        valid
+    });
+
+    match valid {
+        true => Ok(()),
+        false => Err(PasteError::InvalidAttachment),
+    }
+}
@@ -0,0 +1,281 @@
+//! Reference counts of the blobs that hold the content of pastes.
+//!
+//! Blobs are keyed by the hash of their content, so identical content is only stored once. Every row
+//! of `pastr.paste_files` and `pastr.paste_attachments` holds a reference to its blob, counted in
+//! `pastr.blob_refs`. Blobs whose count drops to zero are left in place until [`collect`] removes them.
+//! The same goes for blobs written by transactions that were rolled back, see [`reserve`].
+use crate::storage::{compress, BlobStore, Codec};
+use sqlx::{postgres::PgRow, PgConnection, PgPool, Row};
+use uuid::Uuid;
+
+/// Key and codec of a stored blob, as recorded in `pastr.blob_refs`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StoredBlob {
+    pub(crate) key: String,
+    pub(crate) codec: Codec,
+}
+
+impl StoredBlob {
+    /// Build a [`StoredBlob`] from the `blob_key` and `codec` columns of a row.
+    pub(super) fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            key: row.try_get("blob_key")?,
+            codec: Codec::try_from(row.try_get::<&str, &str>("codec")?)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?,
+        })
+    }
+
+    /// Load the blob from `store`, decompressing it. Returns `None` if there is no such blob.
+    ///
+    /// Decompression happens on the blocking thread pool.
+    pub(super) async fn read(
+        &self,
+        store: &dyn BlobStore,
+    ) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let Some(data) = store.get(&self.key).await? else {
+            return Ok(None);
+        };
+        Ok(Some(self.decode(data).await?))
+    }
+
+    /// Decompress data of the blob as it was read from the store, on the blocking thread pool.
+    pub(super) async fn decode(&self, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+        let codec = self.codec;
+        match codec {
+            Codec::None => Ok(data),
+            _ => Ok(tokio::task::spawn_blocking(move || codec.decode(data)).await??),
+        }
+    }
+}
+
+/// Count blobs that are about to be written, before the transaction that takes references to them begins.
+///
+/// [`acquire`] writes blobs to the store before its transaction commits. If the transaction is rolled back
+/// instead, the committed count of zero keeps the blob known to [`collect`], which then deletes it.
+/// Keys that are counted already are left as they are.
+///
+/// * `keys` - keys of the blobs that will be acquired. Keys may repeat
+/// * `pool` - Connection pool to use for the query, outside of the transaction
+pub(super) async fn reserve<'a>(
+    keys: impl IntoIterator<Item = &'a str>,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut keys: Vec<&str> = keys.into_iter().collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    sqlx::query(
+        "
+        INSERT INTO pastr.blob_refs (key, refs)
+        SELECT key, 0 FROM UNNEST($1::TEXT[]) AS key
+        ON CONFLICT (key) DO NOTHING;
+        ",
+    )
+    .bind(&keys)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Take references to blobs, as part of the transaction that stores the rows referencing them.
+///
+/// Blobs that were not referenced before are written to `store`, since they may not exist or be about
+/// to be collected. They are compressed as the store asks for, recording the codec. The counts are taken
+/// in the order of the keys, so concurrent transactions referencing the same blobs do not deadlock.
+/// The keys must have been passed to [`reserve`] before the transaction began.
+///
+/// * `blobs` - key and content of every reference to take. Keys may repeat
+pub(super) async fn acquire(
+    conn: &mut PgConnection,
+    store: &dyn BlobStore,
+    blobs: impl IntoIterator<Item = (&str, &[u8])>,
+) -> Result<(), anyhow::Error> {
+    let mut blobs: Vec<_> = blobs.into_iter().collect();
+    blobs.sort_unstable_by_key(|(key, _)| *key);
+
+    for chunk in blobs.chunk_by(|(a, _), (b, _)| a == b) {
+        let (key, data) = chunk[0];
+        let count = chunk.len() as i32;
+        let refs: i32 = sqlx::query(
+            "
+            INSERT INTO pastr.blob_refs (key, refs) VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE SET refs = blob_refs.refs + EXCLUDED.refs
+            RETURNING refs;
+            ",
+        )
+        .bind(key)
+        .bind(count)
+        .fetch_one(&mut *conn)
+        .await?
+        .try_get("refs")?;
+
+        if refs == count {
+            let codec = put(store, key, data).await?;
+            sqlx::query("UPDATE pastr.blob_refs SET codec = $2 WHERE key = $1;")
+                .bind(key)
+                .bind(codec.as_str())
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compress `data` as `store` asks for and put it under `key`, returning the codec that was used.
+///
+/// Compression happens on the blocking thread pool.
+async fn put(store: &dyn BlobStore, key: &str, data: &[u8]) -> Result<Codec, anyhow::Error> {
+    let compression = store.compression();
+    if compression.codec == Codec::None || data.len() < compression.min_size {
+        store.put(key, data).await?;
+        return Ok(Codec::None);
+    }
+
+    let data = data.to_vec();
+    let (codec, data) = tokio::task::spawn_blocking(move || {
+        compress(&compression, &data).map(|(codec, compressed)| (codec, compressed.into_owned()))
+    })
+    .await??;
+    store.put(key, &data).await?;
+    Ok(codec)
+}
+
+/// Take further references to blobs that are already referenced, e.g. when copying the rows that reference them.
+///
+/// The rows holding the existing references must be locked by the transaction, so the blobs can not
+/// be collected in the meantime.
+pub(super) async fn share(keys: &[String], conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    let mut keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    keys.sort_unstable();
+
+    for chunk in keys.chunk_by(|a, b| a == b) {
+        sqlx::query("UPDATE pastr.blob_refs SET refs = refs + $2 WHERE key = $1;")
+            .bind(chunk[0])
+            .bind(chunk.len() as i32)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Give up the references that the files and attachments of the given pastes hold,
+/// as part of the transaction that deletes the pastes.
+///
+/// The counts are locked in the order of the keys first, like [`acquire`] takes them.
+pub(super) async fn release_pastes(
+    paste_ids: &[Uuid],
+    conn: &mut PgConnection,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "
+        WITH released AS (
+            SELECT blob_key, COUNT(*) AS count FROM (
+                SELECT blob_key FROM pastr.paste_files WHERE paste_id = ANY($1)
+                UNION ALL
+                SELECT blob_key FROM pastr.paste_attachments WHERE paste_id = ANY($1)
+            ) refs
+            GROUP BY blob_key
+        ),
+        locked AS (
+            SELECT key FROM pastr.blob_refs
+            WHERE key IN (SELECT blob_key FROM released)
+            ORDER BY key
+            FOR UPDATE
+        )
+        UPDATE pastr.blob_refs r SET refs = r.refs - released.count
+        FROM released
+        WHERE r.key = released.blob_key AND r.key IN (SELECT key FROM locked);
+        ",
+    )
+    .bind(paste_ids)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Give up a single reference, as part of the transaction that deletes the row holding it.
+pub(super) async fn release_key(key: &str, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE pastr.blob_refs SET refs = refs - 1 WHERE key = $1;")
+        .bind(key)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Delete the blobs that are no longer referenced from `store`.
+///
+/// Every blob is deleted while its count is locked. A transaction that references the blob again
+/// in the meantime either waits for the deletion and stores the blob anew, or wins the race and
+/// keeps the blob from being collected. Returns the number of deleted blobs.
+///
+/// * `pool` - Connection pool to use for the queries
+/// * `store` - [`BlobStore`] holding the blobs
+pub async fn collect(pool: &PgPool, store: &dyn BlobStore) -> Result<u64, anyhow::Error> {
+    let mut collected = 0;
+
+    loop {
+        let mut tx = pool.begin().await?;
+        let row = sqlx::query(
+            "
+            SELECT key FROM pastr.blob_refs WHERE refs = 0
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED;
+            ",
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(collected);
+        };
+        let key: String = row.try_get("key")?;
+        store.delete(&key).await?;
+        sqlx::query("DELETE FROM pastr.blob_refs WHERE key = $1;")
+            .bind(&key)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        collected += 1;
+    }
+}
+
+/// [`collect`] the blobs released by a deletion that has already been committed.
+///
+/// Failures are only logged, the blobs are collected again by the next run of the reaper.
+pub(super) async fn collect_released(pool: &PgPool, store: &dyn BlobStore) {
+    if let Err(e) = collect(pool, store).await {
+        tracing::error!("failed to delete unreferenced blobs: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{content_key, FilesystemBlobStore};
+
+    #[sqlx::test]
+    async fn collects_blobs_of_rolled_back_transactions(pool: PgPool) {
+        let store = FilesystemBlobStore::new(
+            std::env::temp_dir().join(format!("pastr-test-{}", Uuid::new_v4().simple())),
+        );
+        let data = b"fn main() {}\n";
+        let key = content_key(data);
+
+        reserve([key.as_str()], &pool).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        acquire(&mut tx, &store, [(key.as_str(), &data[..])])
+            .await
+            .unwrap();
+        tx.rollback().await.unwrap();
+        assert!(store.get(&key).await.unwrap().is_some());
+
+        assert_eq!(collect(&pool, &store).await.unwrap(), 1);
+        assert_eq!(store.get(&key).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,401 @@
+use super::blob::{self, StoredBlob};
+use super::PasteError;
+use crate::detect::detect_language;
+use crate::storage::{content_key, BlobStore};
+use sqlx::{postgres::PgRow, PgConnection, PgExecutor, Row};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Longest filename in bytes. Longer names do not fit into the header of a tar archive.
+const MAX_FILENAME_LENGTH: usize = 100;
+
+/// Most files a single paste may hold.
+const MAX_FILES: usize = 50;
+
+/// Values used to create a [`PasteFile`].
+#[derive(Debug, Clone, Default)]
+pub struct NewFile<'a> {
+    /// Required if the paste holds more than one file
+    pub filename: Option<&'a str>,
+    pub content: &'a str,
+    /// Language used for syntax highlighting. Detected from the filename and content if `None`
+    pub language: Option<&'a str>,
+}
+
+/// A single file of a [`Paste`](super::Paste) revision.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasteFile {
+    pub filename: Option<String>,
+    pub content: String,
+    /// Language used for syntax highlighting
+    pub language: Option<String>,
+    /// Confidence of the automatically detected `language`, between 0 and 1.
+    /// `None` if the language was set explicitly
+    pub language_confidence: Option<f32>,
+    /// Blob holding the content in the [`BlobStore`]. `None` until the content is stored.
+    /// Revisions share the blobs of files they did not change
+    pub(crate) blob: Option<StoredBlob>,
+}
+
+/// Columns that are selected to build a [`PasteFile`] via [`PasteFile::from_row`].
+/// Expects `pastr.blob_refs` to be joined on the key of the blob.
+const FILE_COLUMNS: &str = "filename, blob_key, codec, language, language_confidence";
+
+impl PasteFile {
+    /// Build a file from the given values, detecting its language if none is set.
+    ///
+    /// * `file` - values of the file
+    /// * `title` - title of the paste, used for detection if the file has no name
+    pub(super) fn new(file: &NewFile<'_>, title: Option<&str>) -> Self {
+        let (language, language_confidence) = match file.language {
+            Some(language) => (Some(language.to_owned()), None),
+            None => detect(file.filename.or(title), file.content),
+        };
+
+        Self {
+            filename: file.filename.map(str::to_owned),
+            content: file.content.to_owned(),
+            language,
+            language_confidence,
+            blob: None,
+        }
+    }
+
+    /// Build the file of an encrypted paste, see [`PasteKind::Encrypted`](super::PasteKind::Encrypted).
+    ///
+    /// Ciphertext has no language that could be detected.
+    pub(super) fn ciphertext(content: &str) -> Self {
+        Self {
+            filename: None,
+            content: content.to_owned(),
+            language: None,
+            language_confidence: None,
+            blob: None,
+        }
+    }
+
+    /// Replace the content and language of the file. Fields that are `None` are left untouched.
+    ///
+    /// If only the content changes, a previously detected language is detected again,
+    /// while explicitly chosen languages are kept.
+    pub(super) fn change(
+        &mut self,
+        content: Option<&str>,
+        language: Option<&str>,
+        title: Option<&str>,
+    ) {
+        if let Some(content) = content.filter(|content| *content != self.content) {
+            self.content = content.to_owned();
+            self.blob = None;
+        }
+
+        match (language, content) {
+            (Some(language), _) => {
+                self.language = Some(language.to_owned());
+                self.language_confidence = None;
+            }
+            (None, Some(content))
+                if self.language.is_none() || self.language_confidence.is_some() =>
+            {
+                (self.language, self.language_confidence) =
+                    detect(self.filename.as_deref().or(title), content);
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Name under which the file is offered for download.
+    ///
+    /// * `position` - position of the file within its paste, starting at 0
+    pub fn download_name(&self, position: usize) -> String {
+        match &self.filename {
+            Some(filename) => filename.clone(),
+            None => format!("file{}.txt", position + 1),
+        }
+    }
+
+    /// Load the content of a file that was fetched without it, reading its blob from the store only once.
+    ///
+    /// Content that is compressed with a codec whose `Content-Encoding` is accepted by `accepts` is returned as it
+    /// is stored, together with that encoding. Everything else is decompressed.
+    ///
+    /// * `store` - [`BlobStore`] holding the content of the file
+    /// * `accepts` - whether the client accepts a `Content-Encoding`
+    pub async fn load_encoded(
+        &self,
+        store: &dyn BlobStore,
+        accepts: impl Fn(&str) -> bool,
+    ) -> Result<(Option<&'static str>, Vec<u8>), anyhow::Error> {
+        let Some(blob) = &self.blob else {
+            anyhow::bail!("paste file has not been stored");
+        };
+        let Some(data) = store.get(&blob.key).await? else {
+            anyhow::bail!("blob {} of a paste file is missing", blob.key);
+        };
+
+        match blob.codec.content_encoding() {
+            Some(encoding) if accepts(encoding) => Ok((Some(encoding), data)),
+            _ => Ok((None, blob.decode(data).await?)),
+        }
+    }
+
+    /// Load the files of a paste revision, in order.
+    ///
+    /// * `paste_id` - UUID of the paste
+    /// * `revision` - number of the revision
+    /// * `executor` - Connection to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub(super) async fn load<'c>(
+        paste_id: &Uuid,
+        revision: i32,
+        executor: impl PgExecutor<'c>,
+        store: &dyn BlobStore,
+    ) -> Result<Vec<Self>, anyhow::Error> {
+        let mut files = sqlx::query(&format!(
+            "
+            SELECT {FILE_COLUMNS}
+            FROM pastr.paste_files
+            JOIN pastr.blob_refs ON key = blob_key
+            WHERE paste_id = $1 AND revision = $2
+            ORDER BY position;
+            "
+        ))
+        .bind(paste_id)
+        .bind(revision)
+        .fetch_all(executor)
+        .await?
+        .iter()
+        .map(Self::from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+
+        load_contents(files.iter_mut(), store).await?;
+        Ok(files)
+    }
+
+    /// Load the files of the latest revision of several pastes at once, for listings.
+    ///
+    /// Returns the files in order, each paired with the id of its paste.
+    /// Only the metadata of the files is loaded, their `content` is left empty.
+    ///
+    /// * `paste_ids` - UUIDs of the pastes
+    /// * `executor` - Connection to use for the query
+    pub(super) async fn load_latest<'c>(
+        paste_ids: &[Uuid],
+        executor: impl PgExecutor<'c>,
+    ) -> Result<Vec<(Uuid, Self)>, sqlx::Error> {
+        sqlx::query(
+            "
+            SELECT f.paste_id, f.filename, f.blob_key, b.codec, f.language, f.language_confidence
+            FROM pastr.paste_files f
+            JOIN pastr.blob_refs b ON b.key = f.blob_key
+            JOIN pastr.pastes p ON p.id = f.paste_id AND p.revision = f.revision
+            WHERE f.paste_id = ANY($1)
+            ORDER BY f.paste_id, f.position;
+            ",
+        )
+        .bind(paste_ids)
+        .fetch_all(executor)
+        .await?
+        .iter()
+        .map(|row| Ok((row.try_get("paste_id")?, Self::from_row(row)?)))
+        .collect()
+    }
+
+    /// Load the files of all revisions of a paste.
+    ///
+    /// Returns the files in order, each paired with the number of its revision.
+    ///
+    /// * `paste_id` - UUID of the paste
+    /// * `executor` - Connection to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub(super) async fn load_revisions<'c>(
+        paste_id: &Uuid,
+        executor: impl PgExecutor<'c>,
+        store: &dyn BlobStore,
+    ) -> Result<Vec<(i32, Self)>, anyhow::Error> {
+        let mut files = sqlx::query(&format!(
+            "
+            SELECT revision, {FILE_COLUMNS}
+            FROM pastr.paste_files
+            JOIN pastr.blob_refs ON key = blob_key
+            WHERE paste_id = $1
+            ORDER BY revision, position;
+            "
+        ))
+        .bind(paste_id)
+        .fetch_all(executor)
+        .await?
+        .iter()
+        .map(|row| Ok((row.try_get("revision")?, Self::from_row(row)?)))
+        .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        load_contents(files.iter_mut().map(|(_, file)| file), store).await?;
+        Ok(files)
+    }
+
+    /// Store the files of a new revision, as part of the transaction that records the revision.
+    ///
+    /// Files that have no blob yet are stored under the hash of their content, so identical
+    /// content shares a single blob. Every file takes a reference to its blob.
+    pub(super) async fn record(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        paste_id: &Uuid,
+        revision: i32,
+        files: &[Self],
+    ) -> Result<(), anyhow::Error> {
+        let blob_keys: Vec<String> = files
+            .iter()
+            .map(|file| match &file.blob {
+                Some(blob) => blob.key.clone(),
+                None => content_key(file.content.as_bytes()),
+            })
+            .collect();
+        blob::acquire(
+            &mut *conn,
+            store,
+            files
+                .iter()
+                .zip(&blob_keys)
+                .map(|(file, blob_key)| (blob_key.as_str(), file.content.as_bytes())),
+        )
+        .await?;
+
+        for (position, (file, blob_key)) in files.iter().zip(&blob_keys).enumerate() {
+            sqlx::query(
+                "INSERT INTO pastr.paste_files
+                (paste_id, revision, position, filename, blob_key, language, language_confidence)
+                VALUES ($1, $2, $3, $4, $5, $6, $7);",
+            )
+            .bind(paste_id)
+            .bind(revision)
+            .bind(position as i32)
+            .bind(&file.filename)
+            .bind(blob_key)
+            .bind(&file.language)
+            .bind(file.language_confidence)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Build a [`PasteFile`] from a row of the `pastr.paste_files` table, without loading its content.
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            filename: row.try_get("filename")?,
+            content: String::new(),
+            language: row.try_get("language")?,
+            language_confidence: row.try_get("language_confidence")?,
+            blob: Some(StoredBlob::from_row(row)?),
+        })
+    }
+}
+
+/// Load the content of `files` from `store`. Blobs shared by several files are only loaded once.
+async fn load_contents(
+    files: impl Iterator<Item = &mut PasteFile>,
+    store: &dyn BlobStore,
+) -> Result<(), anyhow::Error> {
+    let mut loaded: HashMap<String, String> = HashMap::new();
+
+    for file in files {
+        let Some(blob) = &file.blob else {
+            continue;
+        };
+        if let Some(content) = loaded.get(&blob.key) {
+            file.content = content.clone();
+            continue;
+        }
+
+        let Some(data) = blob.read(store).await? else {
+            anyhow::bail!("blob {} of a paste file is missing", blob.key);
+        };
+        file.content = String::from_utf8(data)?;
+        loaded.insert(blob.key.clone(), file.content.clone());
+    }
+
+    Ok(())
+}
+
+/// Check that the given files can be stored together as one paste.
+///
+/// A paste needs at least one and at most [`MAX_FILES`] files. If it holds more than one,
+/// every file needs a distinct name. Names must not contain path separators or control characters.
+/// Returns [`PasteError::InvalidFiles`] otherwise.
+pub(super) fn validate_files<'a>(
+    mut filenames: impl ExactSizeIterator<Item = Option<&'a str>>,
+) -> Result<(), PasteError> {
+    let count = filenames.len();
+    if count == 0 || count > MAX_FILES {
+        return Err(PasteError::InvalidFiles);
+    }
+
+    let mut seen = Vec::with_capacity(count);
+    let valid = filenames.all(|filename| match filename {
+        Some(filename) => {
+            let valid = is_valid_filename(filename) && !seen.contains(&filename);
+            seen.push(filename);
+            valid
+        }
+        None => count == 1,
+    });
+
+    match valid {
+        true => Ok(()),
+        false => Err(PasteError::InvalidFiles),
+    }
+}
+
+/// Whether `filename` may be used as the name of a [`PasteFile`].
+pub(super) fn is_valid_filename(filename: &str) -> bool {
+    !filename.trim().is_empty()
+        && filename.len() <= MAX_FILENAME_LENGTH
+        && filename != "."
+        && filename != ".."
+        && !filename
+            .chars()
+            .any(|c| c == '/' || c == '\\' || c.is_control())
+}
+
+/// Detect the language of a file, returning it together with its confidence.
+fn detect(filename: Option<&str>, content: &str) -> (Option<String>, Option<f32>) {
+    match detect_language(filename, content) {
+        Some(detected) => (Some(detected.language), Some(detected.confidence)),
+        None => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_file_needs_no_name() {
+        assert!(validate_files([None].into_iter()).is_ok());
+    }
+
+    #[test]
+    fn multiple_files_need_distinct_names() {
+        assert!(validate_files([Some("Cargo.toml"), Some("main.rs")].into_iter()).is_ok());
+        assert!(validate_files([Some("main.rs"), None].into_iter()).is_err());
+        assert!(validate_files([Some("main.rs"), Some("main.rs")].into_iter()).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_file_list() {
+        assert!(validate_files(std::iter::empty()).is_err());
+    }
+
+    #[test]
+    fn rejects_path_like_names() {
+        for filename in ["src/main.rs", "..\\main.rs", "..", " ", "a\nb"] {
+            assert!(
+                validate_files([Some(filename)].into_iter()).is_err(),
+                "{filename:?}"
+            );
+        }
+        assert!(validate_files([Some(&*"a".repeat(101))].into_iter()).is_err());
+    }
+}
@@ -1,3 +1,19 @@
+mod attachment;
+mod blob;
+mod file;
+mod paste;
+mod revision;
+mod slug;
+mod token;
 mod user;
 
-pub use user::{User, UserError};
+pub use attachment::{Attachment, NewAttachment};
+pub use file::{NewFile, PasteFile};
+pub use paste::{
+    CreatedPaste, Editor, Expiry, ForkOrigin, NewFork, NewPaste, Paste, PasteError, PasteKind,
+    PasteUpdate, Visibility,
+};
+pub use revision::PasteRevision;
+pub use slug::is_short_id;
+pub use token::{ApiToken, ApiTokenError, NewApiToken, Scope};
+pub use user::{User, UserError, SESSION_DAYS};
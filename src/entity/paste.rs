@@ -0,0 +1,1151 @@
+use super::attachment::validate_attachments;
+use super::file::validate_files;
+use super::slug::{generate_short_id, is_reserved, is_valid_slug};
+use super::{blob, Attachment, NewAttachment, NewFile, PasteFile, PasteRevision};
+use crate::auth::{generate_token, hash_password, hash_token, verify_password_hash, AuthError};
+use crate::storage::{content_key, BlobStore};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{postgres::PgRow, PgConnection, PgExecutor, PgPool, Row};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Copy, Clone, Error)]
+pub enum PasteError {
+    #[error("paste not found")]
+    PasteNotFound,
+    #[error("invalid edit token")]
+    InvalidEditToken,
+    #[error("private pastes require an owner")]
+    PrivateWithoutOwner,
+    #[error("paste revision not found")]
+    RevisionNotFound,
+    #[error("invalid paste files")]
+    InvalidFiles,
+    #[error("attachment not found")]
+    AttachmentNotFound,
+    #[error("attachments need distinct names and can not be added to burn-after-reading or encrypted pastes")]
+    InvalidAttachment,
+    #[error("encrypted pastes consist of a single unnamed file of ciphertext")]
+    InvalidEncryptedPaste,
+    #[error("slugs need an owner and consist of lowercase words that are not reserved")]
+    InvalidSlug,
+    #[error("slug is already taken")]
+    SlugTaken,
+    #[error("paste exceeds the maximum size")]
+    PasteTooLarge,
+}
+
+/// Attempts to find a free short id before creating a paste fails.
+const SHORT_ID_ATTEMPTS: usize = 5;
+
+/// Lifetime presets that can be chosen for a [`Paste`].
+///
+/// Variants are ordered by their duration, so a preset can be capped with [`Ord::min`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+pub enum Expiry {
+    #[serde(rename = "10m")]
+    TenMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+    #[serde(rename = "1w")]
+    OneWeek,
+    #[serde(rename = "never")]
+    Never,
+}
+
+impl Expiry {
+    /// Return a string representation of the enum value.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::TenMinutes => "10m",
+            Self::OneHour => "1h",
+            Self::OneDay => "1d",
+            Self::OneWeek => "1w",
+            Self::Never => "never",
+        }
+    }
+
+    /// Point in time at which a paste created at `from` expires. `None` if it never does.
+    pub fn expires_at(&self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let lifetime = match self {
+            Self::TenMinutes => Duration::minutes(10),
+            Self::OneHour => Duration::hours(1),
+            Self::OneDay => Duration::days(1),
+            Self::OneWeek => Duration::weeks(1),
+            Self::Never => return None,
+        };
+        Some(from + lifetime)
+    }
+}
+
+/// Determines who is able to see a [`Paste`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed publicly and reachable by everyone
+    Public,
+    /// Not listed, but reachable by everyone who knows the url
+    #[default]
+    Unlisted,
+    /// Only visible to the owner of the paste
+    Private,
+}
+
+impl Visibility {
+    /// Return a string representation of the enum value.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Public => "public",
+            Self::Unlisted => "unlisted",
+            Self::Private => "private",
+        }
+    }
+}
+
+impl TryFrom<&str> for Visibility {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "public" => Ok(Self::Public),
+            "unlisted" => Ok(Self::Unlisted),
+            "private" => Ok(Self::Private),
+            other => Err(format!("{} is not a valid paste visibility", other)),
+        }
+    }
+}
+
+/// Determines what the content of a [`Paste`] is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasteKind {
+    /// Text that the server highlights and serves as it is
+    #[default]
+    Plain,
+    /// Ciphertext produced by the client, which keeps the key to itself.
+    ///
+    /// The paste has a single unnamed file without language, whose content is the base64 encoded
+    /// 12 byte AES-GCM nonce followed by the ciphertext. Title, filenames and languages are part of the
+    /// encrypted data, so the paste itself has none of them and can not have attachments.
+    Encrypted,
+}
+
+impl PasteKind {
+    /// Return a string representation of the enum value.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Plain => "plain",
+            Self::Encrypted => "encrypted",
+        }
+    }
+}
+
+impl TryFrom<&str> for PasteKind {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "encrypted" => Ok(Self::Encrypted),
+            other => Err(format!("{} is not a valid paste kind", other)),
+        }
+    }
+}
+
+/// Shortest ciphertext of an encrypted paste in bytes: the nonce and the authentication tag of AES-GCM.
+const MIN_CIPHERTEXT_LENGTH: usize = 12 + 16;
+
+/// Whether `content` could be the content of an encrypted paste, see [`PasteKind::Encrypted`].
+///
+/// Only checks the encoding and length, the server has no way to tell ciphertext from random data.
+fn is_ciphertext(content: &str) -> bool {
+    let data = content.trim_end_matches('=');
+    content.len().is_multiple_of(4)
+        && content.len() - data.len() <= 2
+        && content.len() >= MIN_CIPHERTEXT_LENGTH.div_ceil(3) * 4
+        && data
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+}
+
+/// Check that the values of a new encrypted paste do not hold anything besides its ciphertext.
+fn validate_encrypted(paste: &NewPaste<'_>) -> Result<(), PasteError> {
+    match paste.files.as_slice() {
+        [file]
+            if paste.title.is_none()
+                && paste.attachments.is_empty()
+                && file.filename.is_none()
+                && file.language.is_none()
+                && is_ciphertext(file.content) =>
+        {
+            Ok(())
+        }
+        _ => Err(PasteError::InvalidEncryptedPaste),
+    }
+}
+
+/// Values used to create a new [`Paste`].
+#[derive(Debug, Clone, Default)]
+pub struct NewPaste<'a> {
+    /// Id of the user creating the paste. `None` for anonymous pastes
+    pub owner: Option<Uuid>,
+    pub title: Option<&'a str>,
+    /// Files of the paste, in the order they are shown. May only be empty if there are `attachments`
+    pub files: Vec<NewFile<'a>>,
+    /// Binary files attached to the paste. Burn-after-reading pastes can not have attachments
+    pub attachments: Vec<NewAttachment<'a>>,
+    /// Point in time after which the paste is no longer available. `None` means the paste never expires
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Delete the paste the first time it is read
+    pub burn_after_reading: bool,
+    /// Private pastes require an `owner`
+    pub visibility: Visibility,
+    /// Hash of the password viewers have to enter, computed via [`Paste::hash_password`].
+    /// `None` for pastes everyone may view
+    pub password_hash: Option<String>,
+    /// Encrypted pastes only hold a single file of ciphertext
+    pub kind: PasteKind,
+    /// Vanity slug under which the paste is reachable as `/{username}/{slug}`. Requires an `owner`
+    pub slug: Option<&'a str>,
+}
+
+/// Changes to apply to an existing [`Paste`]. Fields that are `None` are left untouched.
+///
+/// Applying changes stores the result as a new [`PasteRevision`].
+#[derive(Debug, Clone, Default)]
+pub struct PasteUpdate<'a> {
+    pub title: Option<&'a str>,
+    /// Replaces all files of the paste
+    pub files: Option<Vec<NewFile<'a>>>,
+    /// Replaces the content of the first file
+    pub content: Option<&'a str>,
+    /// Replaces the language of the first file.
+    /// Detected languages are detected again if only the `content` changes
+    pub language: Option<&'a str>,
+}
+
+/// Values used to fork an existing [`Paste`], see [`Paste::fork`].
+#[derive(Debug, Clone, Default)]
+pub struct NewFork {
+    /// Id of the user forking the paste. `None` for anonymous forks
+    pub owner: Option<Uuid>,
+    /// Revision to copy. `None` copies the latest revision
+    pub revision: Option<i32>,
+    /// Point in time after which the fork is no longer available. `None` means the fork never expires
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Private forks require an `owner`
+    pub visibility: Visibility,
+}
+
+/// Paste and revision a [`Paste`] was forked from.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ForkOrigin {
+    /// Short id of the forked paste
+    pub paste: String,
+    pub revision: i32,
+}
+
+/// Result of creating a [`Paste`].
+#[derive(Debug, Clone)]
+pub struct CreatedPaste {
+    pub id: Uuid,
+    /// Id under which the paste is reachable, see [`Paste::short_id`]
+    pub short_id: String,
+    /// Secret that is required to modify or delete the paste. Only its hash is stored
+    pub edit_token: String,
+}
+
+/// A piece of text shared via the application.
+///
+/// Provides functionality to store pastes in the database
+/// and to query or remove them again.
+#[derive(Debug, Clone)]
+pub struct Paste {
+    /// Uuid4 used as primary key in database
+    pub id: Uuid,
+    /// Random base62 id that addresses the paste in urls, like `/aZ3kP9xQ`.
+    /// Pastes created before short ids existed use their uuid
+    pub short_id: String,
+    /// Vanity slug claimed by the owner of the paste
+    pub slug: Option<String>,
+    /// Id of the user that created the paste. `None` if the paste was created anonymously
+    pub owner: Option<Uuid>,
+    pub title: Option<String>,
+    /// Files of the paste, in the order they are shown. Every paste has at least one
+    pub files: Vec<PasteFile>,
+    /// Number of the latest [`PasteRevision`], which the other fields reflect
+    pub revision: i32,
+    pub created_at: DateTime<Utc>,
+    /// Point in time after which the paste is no longer available
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether the paste gets deleted the first time it is read. See [`Paste::burn`]
+    pub burn_after_reading: bool,
+    pub visibility: Visibility,
+    /// Paste this paste was forked from. `None` if it is no fork or the original paste was deleted
+    pub forked_from: Option<ForkOrigin>,
+    /// Hash of the password viewers have to enter. `None` if the paste is not password-protected
+    pub(crate) password_hash: Option<String>,
+    pub kind: PasteKind,
+}
+
+/// Columns that are selected to build a [`Paste`] via [`Paste::from_row`].
+/// Expects the queried table to be `pastr.pastes` without an alias.
+const PASTE_COLUMNS: &str = "id, short_id, slug, owner, title, revision, created_at, expires_at, \
+    burn_after_reading, visibility, forked_from_revision, password_hash, kind, \
+    (SELECT origin.short_id FROM pastr.pastes origin WHERE origin.id = pastes.forked_from) \
+    AS forked_from_short_id";
+
+/// Condition that only matches pastes whose edit token hash is bound to `$2` or whose owner is bound to `$3`.
+const EDITABLE_BY: &str = "(edit_token_hash = $2 OR owner = $3)";
+
+/// Condition that only matches pastes that are visible to the user whose id is bound to `$2`.
+const VISIBLE_TO_VIEWER: &str = "(visibility <> 'private' OR owner = $2)";
+
+/// Proof that a request may modify a [`Paste`].
+#[derive(Debug, Copy, Clone)]
+pub enum Editor<'a> {
+    /// Edit token that was handed out when the paste was created
+    EditToken(&'a str),
+    /// UUID of the user that owns the paste
+    Owner(&'a Uuid),
+}
+
+impl Editor<'_> {
+    fn edit_token_hash(&self) -> Option<String> {
+        match self {
+            Self::EditToken(token) => Some(hash_token(token)),
+            Self::Owner(_) => None,
+        }
+    }
+
+    fn owner(&self) -> Option<&Uuid> {
+        match self {
+            Self::EditToken(_) => None,
+            Self::Owner(owner) => Some(owner),
+        }
+    }
+}
+
+impl Paste {
+    /// Language of the first file, which stands for the whole paste in listings.
+    pub fn language(&self) -> Option<&str> {
+        self.files.first().and_then(|file| file.language.as_deref())
+    }
+
+    /// Whether viewers have to enter a password to see the content of the paste.
+    pub fn is_password_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Hash the password that should protect a new paste, see [`NewPaste::password_hash`].
+    ///
+    /// Uses the same Argon2 setup as the passwords of users. Hashing happens on the blocking thread pool.
+    ///
+    /// * `password` - password viewers have to enter
+    /// * `pepper` - pepper to use for hashing
+    pub async fn hash_password(password: &str, pepper: Vec<u8>) -> Result<String, anyhow::Error> {
+        let password = password.to_owned();
+        let hash = actix_web::rt::task::spawn_blocking(move || {
+            hash_password(password.as_str(), pepper.as_slice())
+        })
+        .await??;
+
+        Ok(hash)
+    }
+
+    /// Check a password entered by a viewer. Pastes that are not password-protected accept any password.
+    ///
+    /// * `password` - password entered by the viewer
+    /// * `pepper` - pepper that was used for hashing
+    pub async fn verify_password(
+        &self,
+        password: &str,
+        pepper: Vec<u8>,
+    ) -> Result<bool, anyhow::Error> {
+        let Some(hash) = self.password_hash.clone() else {
+            return Ok(true);
+        };
+
+        let password = password.to_owned();
+        let result = actix_web::rt::task::spawn_blocking(move || {
+            verify_password_hash(password.as_str(), hash.as_str(), pepper.as_slice())
+        })
+        .await?;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(AuthError::InvalidCredentials(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Create the paste in the database with the specified values.
+    ///
+    /// A new row will be created in the DB via a transaction, together with a fresh edit token
+    /// and the first [`PasteRevision`].
+    /// Files without a language have it detected from their name, or the title of the paste, and content.
+    /// Returns [`PasteError::InvalidFiles`] or [`PasteError::InvalidAttachment`] if the files or attachments
+    /// can not be stored together, otherwise the id and edit token of the new paste or an error when communication with the db fails.
+    ///
+    /// * `paste` - values of the paste to create
+    /// * `pool` - pool to use for storage
+    /// * `store` - [`BlobStore`] to store the content in
+    pub async fn create(
+        paste: &NewPaste<'_>,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<CreatedPaste, anyhow::Error> {
+        Self::insert(paste, None, pool, store).await
+    }
+
+    /// Copy a revision of a paste into a new paste.
+    ///
+    /// The fork records the paste and revision it was copied from, but is otherwise independent of it.
+    /// Attachments are not part of revisions, so the fork gets a copy of the current ones.
+    /// Burn-after-reading pastes can not be forked, since that would keep their content around.
+    /// Returns [`PasteError::PasteNotFound`] if the paste is not visible to the forking user
+    /// and [`PasteError::RevisionNotFound`] if the requested revision does not exist.
+    ///
+    /// * `short_id` - short id of the paste to fork
+    /// * `fork` - values of the fork to create
+    /// * `pool` - pool to use for storage
+    /// * `store` - [`BlobStore`] holding the content of the paste and its fork
+    pub async fn fork(
+        short_id: &str,
+        fork: &NewFork,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<CreatedPaste, anyhow::Error> {
+        let original = Self::fetch(short_id, fork.owner.as_ref(), pool, store).await?;
+        if original.burn_after_reading {
+            return Err(anyhow::anyhow!(PasteError::PasteNotFound));
+        }
+
+        let revision = fork.revision.unwrap_or(original.revision);
+        let revision = PasteRevision::fetch(&original.id, revision, pool, store).await?;
+        let files = revision
+            .files
+            .iter()
+            .map(|file| NewFile {
+                filename: file.filename.as_deref(),
+                content: &file.content,
+                // detected languages are detected again, so they are not mistaken for explicitly chosen ones
+                language: match file.language_confidence {
+                    Some(_) => None,
+                    None => file.language.as_deref(),
+                },
+            })
+            .collect();
+
+        let paste = NewPaste {
+            owner: fork.owner,
+            title: revision.title.as_deref(),
+            files,
+            attachments: Vec::new(),
+            expires_at: fork.expires_at,
+            burn_after_reading: false,
+            visibility: fork.visibility,
+            // the forking user knows the content already, the fork is theirs to share
+            password_hash: None,
+            kind: original.kind,
+            slug: None,
+        };
+        Self::insert(&paste, Some((&original.id, revision.revision)), pool, store).await
+    }
+
+    /// List the forks of a paste that are public or owned by `viewer`, newest first.
+    ///
+    /// Unlisted forks are left out, like in the listing of public pastes, so they can only be found by their url.
+    /// Files are listed without their content.
+    ///
+    /// * `id` - UUID of the forked paste
+    /// * `viewer` - UUID of the user requesting the forks. `None` for anonymous requests
+    /// * `pool` - Connection pool to use for the query
+    pub async fn list_forks(
+        id: &Uuid,
+        viewer: Option<&Uuid>,
+        pool: &PgPool,
+    ) -> Result<Vec<Self>, anyhow::Error> {
+        let rows = sqlx::query(&format!(
+            "
+            SELECT {PASTE_COLUMNS}
+            FROM pastr.pastes
+            WHERE forked_from = $1 AND (expires_at IS NULL OR expires_at > NOW())
+                AND (visibility = 'public' OR owner = $2)
+            ORDER BY created_at DESC;
+            "
+        ))
+        .bind(id)
+        .bind(viewer)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::from_rows(&rows, pool).await?)
+    }
+
+    /// Store a new paste, shared by [`Paste::create`] and [`Paste::fork`].
+    ///
+    /// The paste gets a random short id, a new one is generated as long as the id is taken.
+    ///
+    /// * `forked_from` - UUID and revision of the forked paste
+    async fn insert(
+        paste: &NewPaste<'_>,
+        forked_from: Option<(&Uuid, i32)>,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<CreatedPaste, anyhow::Error> {
+        if paste.visibility == Visibility::Private && paste.owner.is_none() {
+            return Err(anyhow::anyhow!(PasteError::PrivateWithoutOwner));
+        }
+        match paste.files.is_empty() {
+            // forks of pastes that only consist of attachments get theirs copied below
+            true if paste.attachments.is_empty() && forked_from.is_none() => {
+                return Err(anyhow::anyhow!(PasteError::InvalidFiles));
+            }
+            true => {}
+            false => validate_files(paste.files.iter().map(|file| file.filename))?,
+        }
+        if !paste.attachments.is_empty() {
+            if paste.burn_after_reading {
+                return Err(anyhow::anyhow!(PasteError::InvalidAttachment));
+            }
+            validate_attachments(&paste.attachments)?;
+        }
+        if paste.kind == PasteKind::Encrypted {
+            validate_encrypted(paste)?;
+        }
+
+        let files: Vec<_> = paste
+            .files
+            .iter()
+            .map(|file| match paste.kind {
+                PasteKind::Plain => PasteFile::new(file, paste.title),
+                PasteKind::Encrypted => PasteFile::ciphertext(file.content),
+            })
+            .collect();
+
+        let blob_keys: Vec<String> = files
+            .iter()
+            .map(|file| file.content.as_bytes())
+            .chain(paste.attachments.iter().map(|attachment| attachment.data))
+            .map(content_key)
+            .collect();
+        blob::reserve(blob_keys.iter().map(String::as_str), pool).await?;
+
+        let mut tx = pool.begin().await?;
+        let id = Uuid::new_v4();
+        let edit_token = generate_token();
+
+        if let Some(slug) = paste.slug {
+            let Some(owner) = paste.owner else {
+                return Err(anyhow::anyhow!(PasteError::InvalidSlug));
+            };
+            Self::check_slug(&owner, slug, &mut tx).await?;
+        }
+
+        let mut short_id = None;
+        for _ in 0..SHORT_ID_ATTEMPTS {
+            let candidate = generate_short_id();
+            let result = sqlx::query(
+                "INSERT INTO pastr.pastes
+                (id, short_id, slug, owner, title, expires_at, burn_after_reading, visibility,
+                    edit_token_hash, forked_from, forked_from_revision, password_hash, kind)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                ON CONFLICT (short_id) DO NOTHING;",
+            )
+            .bind(id)
+            .bind(&candidate)
+            .bind(paste.slug)
+            .bind(paste.owner)
+            .bind(paste.title)
+            .bind(paste.expires_at)
+            .bind(paste.burn_after_reading)
+            .bind(paste.visibility.as_str())
+            .bind(hash_token(&edit_token))
+            .bind(forked_from.map(|(paste, _)| paste))
+            .bind(forked_from.map(|(_, revision)| revision))
+            .bind(&paste.password_hash)
+            .bind(paste.kind.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 1 {
+                short_id = Some(candidate);
+                break;
+            }
+            tracing::debug!("short id {} is taken, generating another one", candidate);
+        }
+        let Some(short_id) = short_id else {
+            anyhow::bail!(
+                "no free short id found after {} attempts",
+                SHORT_ID_ATTEMPTS
+            );
+        };
+
+        PasteRevision::record(&mut tx, store, &id, 1, paste.title, &files).await?;
+        Attachment::record(&mut tx, store, &id, &paste.attachments).await?;
+        if let Some((origin, _)) = forked_from {
+            Attachment::copy(&mut tx, origin, &id).await?;
+        }
+
+        tx.commit().await?;
+        Ok(CreatedPaste {
+            id,
+            short_id,
+            edit_token,
+        })
+    }
+
+    /// Check that `owner` may claim `slug` for a new paste.
+    ///
+    /// Returns [`PasteError::InvalidSlug`] if the slug, or the username of the owner, is not usable in urls
+    /// and [`PasteError::SlugTaken`] if another paste of the owner uses the slug already.
+    async fn check_slug(
+        owner: &Uuid,
+        slug: &str,
+        conn: &mut PgConnection,
+    ) -> Result<(), anyhow::Error> {
+        if !is_valid_slug(slug) {
+            return Err(anyhow::anyhow!(PasteError::InvalidSlug));
+        }
+
+        let username: Option<String> =
+            sqlx::query("SELECT username FROM pastr.users WHERE id = $1;")
+                .bind(owner)
+                .fetch_optional(&mut *conn)
+                .await?
+                .map(|row| row.try_get("username"))
+                .transpose()?;
+        // users registered before their name became reserved can not have vanity urls
+        if username.as_deref().is_none_or(is_reserved) {
+            return Err(anyhow::anyhow!(PasteError::InvalidSlug));
+        }
+
+        let taken = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM pastr.pastes WHERE owner = $1 AND slug = $2);",
+        )
+        .bind(owner)
+        .bind(slug)
+        .fetch_one(&mut *conn)
+        .await?
+        .try_get::<bool, &str>("exists")?;
+        if taken {
+            return Err(anyhow::anyhow!(PasteError::SlugTaken));
+        }
+
+        Ok(())
+    }
+
+    /// Fetch the paste with the given short id from the database.
+    ///
+    /// Pastes that have already expired or that are not visible to `viewer` are treated as if they do not exist.
+    /// Fetching does not delete burn-after-reading pastes, use [`Paste::burn`] to read those.
+    /// Returns [`PasteError::PasteNotFound`] if there is no such paste.
+    ///
+    /// * `short_id` - short id of the paste to fetch, see [`Paste::short_id`]
+    /// * `viewer` - UUID of the user requesting the paste. `None` for anonymous requests
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub async fn fetch(
+        short_id: &str,
+        viewer: Option<&Uuid>,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<Self, anyhow::Error> {
+        let row = Self::fetch_row(short_id, viewer, pool).await?;
+        let mut paste = Self::from_row(&row)?;
+        paste.files = PasteFile::load(&paste.id, paste.revision, pool, store).await?;
+
+        Ok(paste)
+    }
+
+    /// Fetch a paste like [`Paste::fetch`], but without the content of its files.
+    ///
+    /// For serving single files, whose content is then loaded via [`PasteFile::load_encoded`].
+    ///
+    /// * `short_id` - short id of the paste to fetch, see [`Paste::short_id`]
+    /// * `viewer` - UUID of the user requesting the paste. `None` for anonymous requests
+    /// * `pool` - Connection pool to use for the query
+    pub async fn fetch_metadata(
+        short_id: &str,
+        viewer: Option<&Uuid>,
+        pool: &PgPool,
+    ) -> Result<Self, anyhow::Error> {
+        let row = Self::fetch_row(short_id, viewer, pool).await?;
+        Self::from_rows(std::slice::from_ref(&row), pool)
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!(PasteError::PasteNotFound))
+    }
+
+    /// Fetch the row of a paste for [`Paste::fetch`] and [`Paste::fetch_metadata`].
+    async fn fetch_row(
+        short_id: &str,
+        viewer: Option<&Uuid>,
+        pool: &PgPool,
+    ) -> Result<PgRow, anyhow::Error> {
+        let row = sqlx::query(&format!(
+            "
+            SELECT {PASTE_COLUMNS}
+            FROM pastr.pastes
+            WHERE short_id = $1 AND (expires_at IS NULL OR expires_at > NOW())
+                AND {VISIBLE_TO_VIEWER};
+            "
+        ))
+        .bind(short_id)
+        .bind(viewer)
+        .fetch_optional(pool)
+        .await?;
+
+        row.ok_or_else(|| anyhow::anyhow!(PasteError::PasteNotFound))
+    }
+
+    /// Read and delete a burn-after-reading paste.
+    ///
+    /// The paste is locked while it is read and deleted, so only one of several concurrent readers
+    /// receives the paste. Returns [`PasteError::PasteNotFound`] if there is no such paste,
+    /// if it is not visible to `viewer` or if the paste is not marked as burn-after-reading.
+    ///
+    /// * `id` - UUID of the paste to read
+    /// * `viewer` - UUID of the user requesting the paste. `None` for anonymous requests
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub async fn burn(
+        id: &Uuid,
+        viewer: Option<&Uuid>,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<Self, anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        let row = sqlx::query(&format!(
+            "
+            SELECT {PASTE_COLUMNS}
+            FROM pastr.pastes
+            WHERE id = $1 AND burn_after_reading
+                AND (expires_at IS NULL OR expires_at > NOW())
+                AND {VISIBLE_TO_VIEWER}
+            FOR UPDATE;
+            "
+        ))
+        .bind(id)
+        .bind(viewer)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Err(anyhow::anyhow!(PasteError::PasteNotFound));
+        };
+        let mut paste = Self::from_row(&row)?;
+        paste.files = PasteFile::load(&paste.id, paste.revision, &mut *tx, store).await?;
+        blob::release_pastes(&[paste.id], &mut tx).await?;
+
+        sqlx::query("DELETE FROM pastr.pastes WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        blob::collect_released(pool, store).await;
+        Ok(paste)
+    }
+
+    /// List the most recently created public pastes, newest first.
+    ///
+    /// Burn-after-reading pastes are never listed, since listing them would invite strangers to destroy them.
+    /// Files are listed without their content.
+    ///
+    /// * `limit` - maximum number of pastes to return
+    /// * `pool` - Connection pool to use for the query
+    pub async fn list_public(limit: i64, pool: &PgPool) -> Result<Vec<Self>, anyhow::Error> {
+        let rows = sqlx::query(&format!(
+            "
+            SELECT {PASTE_COLUMNS}
+            FROM pastr.pastes
+            WHERE visibility = 'public' AND NOT burn_after_reading
+                AND (expires_at IS NULL OR expires_at > NOW())
+            ORDER BY created_at DESC
+            LIMIT $1;
+            "
+        ))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::from_rows(&rows, pool).await?)
+    }
+
+    /// Lock a paste for a change by `editor`, as part of the transaction that modifies it.
+    ///
+    /// Returns the UUID of the paste, [`PasteError::PasteNotFound`] if there is no such paste or it is a private
+    /// paste of someone else, and [`PasteError::InvalidEditToken`] if `editor` may not modify it.
+    pub(super) async fn lock_for_edit(
+        short_id: &str,
+        editor: Editor<'_>,
+        conn: &mut PgConnection,
+    ) -> Result<Uuid, anyhow::Error> {
+        let row = sqlx::query(&format!(
+            "
+            SELECT id FROM pastr.pastes
+            WHERE short_id = $1 AND (expires_at IS NULL OR expires_at > NOW()) AND {EDITABLE_BY}
+            FOR UPDATE;
+            "
+        ))
+        .bind(short_id)
+        .bind(editor.edit_token_hash())
+        .bind(editor.owner())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(row) = row {
+            return Ok(row.try_get("id")?);
+        }
+
+        // private pastes of others must not be told apart from pastes that do not exist
+        let exists = sqlx::query(&format!(
+            "
+            SELECT 1 FROM pastr.pastes
+            WHERE short_id = $1 AND (expires_at IS NULL OR expires_at > NOW()) AND {VISIBLE_TO_VIEWER};
+            "
+        ))
+        .bind(short_id)
+        .bind(editor.owner())
+        .fetch_optional(&mut *conn)
+        .await?;
+        match exists {
+            Some(_) => Err(anyhow::anyhow!(PasteError::InvalidEditToken)),
+            None => Err(anyhow::anyhow!(PasteError::PasteNotFound)),
+        }
+    }
+
+    /// Find the short id of the paste that claimed a vanity slug.
+    ///
+    /// Returns [`PasteError::PasteNotFound`] if the user has no paste with this slug or it is not visible to `viewer`.
+    ///
+    /// * `username` - name of the owner of the paste
+    /// * `slug` - vanity slug of the paste
+    /// * `viewer` - UUID of the user requesting the paste. `None` for anonymous requests
+    /// * `pool` - Connection pool to use for the query
+    pub async fn find_slug(
+        username: &str,
+        slug: &str,
+        viewer: Option<&Uuid>,
+        pool: &PgPool,
+    ) -> Result<String, anyhow::Error> {
+        let short_id = sqlx::query(&format!(
+            "
+            SELECT short_id FROM pastr.pastes
+            JOIN pastr.users ON users.id = pastes.owner
+            WHERE users.username = $1 AND pastes.slug = $3
+                AND (expires_at IS NULL OR expires_at > NOW())
+                AND {VISIBLE_TO_VIEWER};
+            "
+        ))
+        .bind(username)
+        .bind(viewer)
+        .bind(slug)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| row.try_get::<String, &str>("short_id"))
+        .transpose()?;
+
+        short_id.ok_or_else(|| anyhow::anyhow!(PasteError::PasteNotFound))
+    }
+
+    /// Apply the given changes to a paste.
+    ///
+    /// The result is stored as a new [`PasteRevision`], unless nothing actually changed.
+    /// If the content changes without an explicit language, a previously detected language is detected again.
+    /// Returns [`PasteError::PasteNotFound`] if there is no such paste, [`PasteError::InvalidEditToken`]
+    /// if `editor` may not change it, [`PasteError::InvalidFiles`] if the new files can not be stored together
+    /// and [`PasteError::PasteTooLarge`] if the files of the changed paste exceed `max_size` in total.
+    ///
+    /// * `short_id` - short id of the paste to update
+    /// * `editor` - edit token or owner that proves the change is allowed
+    /// * `update` - changes to apply
+    /// * `max_size` - maximum total size of the files in bytes
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub async fn update(
+        short_id: &str,
+        editor: Editor<'_>,
+        update: &PasteUpdate<'_>,
+        max_size: usize,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<(), anyhow::Error> {
+        let blob_keys: Vec<String> = update
+            .files
+            .iter()
+            .flatten()
+            .map(|file| file.content)
+            .chain(update.content)
+            .map(|content| content_key(content.as_bytes()))
+            .collect();
+        blob::reserve(blob_keys.iter().map(String::as_str), pool).await?;
+
+        let mut tx = pool.begin().await?;
+        let id = &Self::lock_for_edit(short_id, editor, &mut tx).await?;
+
+        let row = sqlx::query(
+            "
+            SELECT title, revision, kind FROM pastr.pastes
+            WHERE id = $1 AND (expires_at IS NULL OR expires_at > NOW())
+            FOR UPDATE;
+            ",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Err(anyhow::anyhow!(PasteError::PasteNotFound));
+        };
+        let old_title: Option<String> = row.try_get("title")?;
+        let old_revision: i32 = row.try_get("revision")?;
+        let kind = PasteKind::try_from(row.try_get::<&str, &str>("kind")?)
+            .map_err(|e| sqlx::Error::Decode(e.into()))?;
+        // only the ciphertext of encrypted pastes can be replaced, everything else is part of it
+        if kind == PasteKind::Encrypted
+            && (update.title.is_some()
+                || update.files.is_some()
+                || update.language.is_some()
+                || update
+                    .content
+                    .is_some_and(|content| !is_ciphertext(content)))
+        {
+            return Err(anyhow::anyhow!(PasteError::InvalidEncryptedPaste));
+        }
+        let old_files = PasteFile::load(id, old_revision, &mut *tx, store).await?;
+
+        let title = update.title.or(old_title.as_deref());
+        let mut files: Vec<_> = match &update.files {
+            Some(files) => {
+                validate_files(files.iter().map(|file| file.filename))?;
+                files
+                    .iter()
+                    .map(|file| PasteFile::new(file, title))
+                    .collect()
+            }
+            None => old_files.clone(),
+        };
+        if let Some(first) = files.first_mut() {
+            match (kind, update.content) {
+                (PasteKind::Plain, _) => first.change(update.content, update.language, title),
+                (PasteKind::Encrypted, Some(content)) => *first = PasteFile::ciphertext(content),
+                (PasteKind::Encrypted, None) => {}
+            }
+        }
+        // unchanged content keeps the key of its blob, so an update that changes nothing is recognized
+        for file in files.iter_mut().filter(|file| file.blob.is_none()) {
+            if let Some(old) = old_files.iter().find(|old| old.content == file.content) {
+                file.blob = old.blob.clone();
+            }
+        }
+
+        if title == old_title.as_deref() && files == old_files {
+            return Ok(());
+        }
+
+        // new content of the first file is stored next to the other files, which count towards the limit as well
+        if files.iter().map(|file| file.content.len()).sum::<usize>() > max_size {
+            return Err(anyhow::anyhow!(PasteError::PasteTooLarge));
+        }
+
+        let revision = old_revision + 1;
+        sqlx::query("UPDATE pastr.pastes SET title = $2, revision = $3 WHERE id = $1;")
+            .bind(id)
+            .bind(title)
+            .bind(revision)
+            .execute(&mut *tx)
+            .await?;
+
+        PasteRevision::record(&mut tx, store, id, revision, title, &files).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Delete all pastes whose expiration date has passed, together with the blobs nothing references anymore.
+    ///
+    /// Blobs left unreferenced by earlier deletions whose collection failed are deleted as well.
+    /// Returns the number of deleted pastes.
+    ///
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the pastes
+    pub async fn delete_expired(
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<u64, anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        let ids: Vec<Uuid> =
+            sqlx::query("SELECT id FROM pastr.pastes WHERE expires_at <= NOW() FOR UPDATE;")
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|row| row.try_get("id"))
+                .collect::<Result<_, _>>()?;
+        blob::release_pastes(&ids, &mut tx).await?;
+
+        let result = sqlx::query("DELETE FROM pastr.pastes WHERE id = ANY($1);")
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        let collected = blob::collect(pool, store).await?;
+        if collected > 0 {
+            tracing::info!("deleted {} unreferenced blobs", collected);
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Delete a paste, together with the blobs nothing references anymore.
+    ///
+    /// Returns [`PasteError::PasteNotFound`] if there was no paste to delete
+    /// and [`PasteError::InvalidEditToken`] if `editor` may not delete it.
+    ///
+    /// * `short_id` - short id of the paste to delete
+    /// * `editor` - edit token or owner that proves the deletion is allowed
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the paste
+    pub async fn delete(
+        short_id: &str,
+        editor: Editor<'_>,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+        let id = Self::lock_for_edit(short_id, editor, &mut tx).await?;
+        blob::release_pastes(&[id], &mut tx).await?;
+
+        sqlx::query("DELETE FROM pastr.pastes WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        blob::collect_released(pool, store).await;
+        Ok(())
+    }
+
+    /// Build pastes from rows of the `pastr.pastes` table, together with their files.
+    async fn from_rows<'c>(
+        rows: &[PgRow],
+        executor: impl PgExecutor<'c>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let mut pastes = rows
+            .iter()
+            .map(Self::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        let ids: Vec<_> = pastes.iter().map(|paste| paste.id).collect();
+
+        for (paste_id, file) in PasteFile::load_latest(&ids, executor).await? {
+            if let Some(paste) = pastes.iter_mut().find(|paste| paste.id == paste_id) {
+                paste.files.push(file);
+            }
+        }
+
+        Ok(pastes)
+    }
+
+    /// Build a [`Paste`] from a row of the `pastr.pastes` table, without loading its files.
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            short_id: row.try_get("short_id")?,
+            slug: row.try_get("slug")?,
+            owner: row.try_get("owner")?,
+            title: row.try_get("title")?,
+            files: Vec::new(),
+            revision: row.try_get("revision")?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+            burn_after_reading: row.try_get("burn_after_reading")?,
+            visibility: Visibility::try_from(row.try_get::<&str, &str>("visibility")?)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?,
+            forked_from: match (
+                row.try_get("forked_from_short_id")?,
+                row.try_get("forked_from_revision")?,
+            ) {
+                (Some(paste), Some(revision)) => Some(ForkOrigin { paste, revision }),
+                _ => None,
+            },
+            password_hash: row.try_get("password_hash")?,
+            kind: PasteKind::try_from(row.try_get::<&str, &str>("kind")?)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_is_capped_by_maximum() {
+        assert_eq!(Expiry::Never.min(Expiry::OneDay), Expiry::OneDay);
+        assert_eq!(Expiry::TenMinutes.min(Expiry::OneDay), Expiry::TenMinutes);
+    }
+
+    #[test]
+    fn never_expires() {
+        assert_eq!(Expiry::Never.expires_at(Utc::now()), None);
+    }
+
+    #[test]
+    fn ciphertext_is_base64_of_nonce_and_tag() {
+        // 28 zero bytes, the shortest possible ciphertext
+        assert!(is_ciphertext("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=="));
+        assert!(is_ciphertext(
+            "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJCrze8S"
+        ));
+        assert!(!is_ciphertext("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"));
+        assert!(!is_ciphertext("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="));
+        assert!(!is_ciphertext("hello world, this is not encrypted at all"));
+    }
+
+    #[test]
+    fn encrypted_paste_only_holds_ciphertext() {
+        let ciphertext = "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJCrze8S";
+        let file = NewFile {
+            filename: None,
+            content: ciphertext,
+            language: None,
+        };
+        let paste = NewPaste {
+            files: vec![file.clone()],
+            kind: PasteKind::Encrypted,
+            ..Default::default()
+        };
+        assert!(validate_encrypted(&paste).is_ok());
+
+        let titled = NewPaste {
+            title: Some("leaks"),
+            ..paste.clone()
+        };
+        assert!(validate_encrypted(&titled).is_err());
+
+        let named = NewPaste {
+            files: vec![NewFile {
+                filename: Some("secret.txt"),
+                ..file
+            }],
+            ..paste
+        };
+        assert!(validate_encrypted(&named).is_err());
+    }
+
+    #[test]
+    fn expires_after_preset() {
+        let now = Utc::now();
+
+        assert_eq!(
+            Expiry::OneHour.expires_at(now),
+            Some(now + Duration::hours(1))
+        );
+    }
+}
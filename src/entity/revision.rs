@@ -0,0 +1,128 @@
+use super::{PasteError, PasteFile};
+use crate::storage::BlobStore;
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, PgConnection, PgPool, Row};
+use uuid::Uuid;
+
+/// Immutable version of a [`Paste`](super::Paste).
+///
+/// Every paste starts out with revision 1. Each edit stores the resulting
+/// title and files as the next revision.
+#[derive(Debug, Clone)]
+pub struct PasteRevision {
+    pub paste_id: Uuid,
+    /// Number of the revision, counting up from 1
+    pub revision: i32,
+    pub title: Option<String>,
+    /// Files of the revision, in the order they are shown
+    pub files: Vec<PasteFile>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PasteRevision {
+    /// List all revisions of a paste, newest first.
+    ///
+    /// Does not check whether the paste may be viewed, fetch the paste itself for that.
+    ///
+    /// * `paste_id` - UUID of the paste
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub async fn list(
+        paste_id: &Uuid,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<Vec<Self>, anyhow::Error> {
+        let rows = sqlx::query(
+            "
+            SELECT paste_id, revision, title, created_at
+            FROM pastr.paste_revisions
+            WHERE paste_id = $1
+            ORDER BY revision DESC;
+            ",
+        )
+        .bind(paste_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut revisions = rows
+            .iter()
+            .map(Self::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        for (number, file) in PasteFile::load_revisions(paste_id, pool, store).await? {
+            if let Some(revision) = revisions.iter_mut().find(|r| r.revision == number) {
+                revision.files.push(file);
+            }
+        }
+
+        Ok(revisions)
+    }
+
+    /// Fetch a single revision of a paste.
+    ///
+    /// Does not check whether the paste may be viewed, fetch the paste itself for that.
+    /// Returns [`PasteError::RevisionNotFound`] if there is no such revision.
+    ///
+    /// * `paste_id` - UUID of the paste
+    /// * `revision` - number of the revision
+    /// * `pool` - Connection pool to use for the query
+    /// * `store` - [`BlobStore`] holding the content of the files
+    pub async fn fetch(
+        paste_id: &Uuid,
+        revision: i32,
+        pool: &PgPool,
+        store: &dyn BlobStore,
+    ) -> Result<Self, anyhow::Error> {
+        let row = sqlx::query(
+            "
+            SELECT paste_id, revision, title, created_at
+            FROM pastr.paste_revisions
+            WHERE paste_id = $1 AND revision = $2;
+            ",
+        )
+        .bind(paste_id)
+        .bind(revision)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Err(anyhow::anyhow!(PasteError::RevisionNotFound));
+        };
+        let mut revision = Self::from_row(&row)?;
+        revision.files = PasteFile::load(paste_id, revision.revision, pool, store).await?;
+
+        Ok(revision)
+    }
+
+    /// Store a new revision of a paste, as part of the transaction that creates or changes the paste.
+    pub(super) async fn record(
+        conn: &mut PgConnection,
+        store: &dyn BlobStore,
+        paste_id: &Uuid,
+        revision: i32,
+        title: Option<&str>,
+        files: &[PasteFile],
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "INSERT INTO pastr.paste_revisions (paste_id, revision, title)
+            VALUES ($1, $2, $3);",
+        )
+        .bind(paste_id)
+        .bind(revision)
+        .bind(title)
+        .execute(&mut *conn)
+        .await?;
+
+        PasteFile::record(conn, store, paste_id, revision, files).await
+    }
+
+    /// Build a [`PasteRevision`] from a row of the `pastr.paste_revisions` table, without loading its files.
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            paste_id: row.try_get("paste_id")?,
+            revision: row.try_get("revision")?,
+            title: row.try_get("title")?,
+            files: Vec::new(),
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
@@ -0,0 +1,117 @@
+//! Identifiers that show up in the urls of pastes.
+//!
+//! Every paste is addressed by a short id like `/aZ3kP9xQ`. Pastes of logged in users can additionally
+//! claim a vanity slug, which makes them reachable as `/{username}/{slug}`.
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+
+/// Number of base62 characters of a short id, which leaves room for about 2 * 10^14 pastes.
+const SHORT_ID_LENGTH: usize = 8;
+
+/// Longest vanity slug in bytes.
+const MAX_SLUG_LENGTH: usize = 64;
+
+/// Path segments taken by routes of the application.
+///
+/// Neither usernames nor slugs may use them, otherwise `/{username}/{slug}` could point at an existing route
+/// like `/static/...` or `/{id}/raw`. Short ids are checked as well, although hitting one is very unlikely.
+const RESERVED_WORDS: &[&str] = &[
+    "about",
+    "activate",
+    "api",
+    "attachments",
+    "diff",
+    "download",
+    "encrypt",
+    "fork",
+    "forks",
+    "healthcheck",
+    "login",
+    "logout",
+    "notfound",
+    "raw",
+    "register",
+    "reset",
+    "revisions",
+    "static",
+    "unlock",
+];
+
+/// Generate a random base62 short id that is not a reserved word.
+///
+/// Short ids are not guaranteed to be unique, callers have to retry with a new one on collisions.
+pub(super) fn generate_short_id() -> String {
+    loop {
+        let id = Alphanumeric.sample_string(&mut OsRng, SHORT_ID_LENGTH);
+        if !is_reserved(&id) {
+            return id;
+        }
+    }
+}
+
+/// Whether `id` looks like the id of a paste, either a short id or the uuid of a paste created before short ids.
+///
+/// Only a cheap syntactic check for ids that end up in urls, it says nothing about whether the paste exists.
+pub fn is_short_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Whether `word` is taken by a route and can not be used as username or slug. Ignores case.
+pub(super) fn is_reserved(word: &str) -> bool {
+    RESERVED_WORDS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(word))
+}
+
+/// Whether `slug` can be claimed as vanity slug.
+///
+/// Slugs consist of lowercase letters and digits, separated by single hyphens, and must not be reserved words.
+pub(super) fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.len() <= MAX_SLUG_LENGTH
+        && slug.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        })
+        && !is_reserved(slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_ids_are_base62() {
+        let id = generate_short_id();
+
+        assert_eq!(id.len(), SHORT_ID_LENGTH);
+        assert!(id.bytes().all(|b| b.is_ascii_alphanumeric()));
+        assert!(is_short_id(&id));
+        assert!(is_short_id("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!is_short_id("\\evil.example"));
+        assert!(!is_short_id(""));
+    }
+
+    #[test]
+    fn slugs_are_lowercase_words() {
+        assert!(is_valid_slug("deploy-notes"));
+        assert!(is_valid_slug("2024"));
+        assert!(!is_valid_slug(""));
+        assert!(!is_valid_slug("Deploy-Notes"));
+        assert!(!is_valid_slug("deploy--notes"));
+        assert!(!is_valid_slug("-deploy"));
+        assert!(!is_valid_slug("deploy notes"));
+        assert!(!is_valid_slug("../etc"));
+        assert!(!is_valid_slug(&"a".repeat(MAX_SLUG_LENGTH + 1)));
+    }
+
+    #[test]
+    fn routes_are_reserved() {
+        assert!(is_reserved("static"));
+        assert!(is_reserved("Register"));
+        assert!(!is_valid_slug("raw"));
+        assert!(!is_reserved("leon"));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::auth::{generate_token, hash_token};
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, PgPool, Row};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Prefix of api tokens, which makes leaked tokens easy to recognize, e.g. by secret scanners.
+const TOKEN_PREFIX: &str = "pastr_";
+
+/// Longest name of an api token in bytes.
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Debug, Copy, Clone, Error)]
+pub enum ApiTokenError {
+    #[error("api token needs a name, at least one scope and an expiry in the future")]
+    InvalidApiToken,
+    #[error("api token not found")]
+    ApiTokenNotFound,
+}
+
+/// Permission granted to an [`ApiToken`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Scope {
+    /// View pastes, including private pastes of the user
+    #[serde(rename = "paste:read")]
+    PasteRead,
+    /// Create, fork and edit pastes owned by the user
+    #[serde(rename = "paste:write")]
+    PasteWrite,
+    /// Delete pastes owned by the user
+    #[serde(rename = "paste:delete")]
+    PasteDelete,
+}
+
+impl Scope {
+    /// Return a string representation of the enum value.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::PasteRead => "paste:read",
+            Self::PasteWrite => "paste:write",
+            Self::PasteDelete => "paste:delete",
+        }
+    }
+}
+
+impl TryFrom<&str> for Scope {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "paste:read" => Ok(Self::PasteRead),
+            "paste:write" => Ok(Self::PasteWrite),
+            "paste:delete" => Ok(Self::PasteDelete),
+            other => Err(format!("{} is not a valid api token scope", other)),
+        }
+    }
+}
+
+/// Values used to create a new [`ApiToken`].
+#[derive(Debug, Clone)]
+pub struct NewApiToken<'a> {
+    /// Tells the tokens of a user apart, e.g. the job that uses it
+    pub name: &'a str,
+    pub scopes: &'a [Scope],
+    /// `None` for tokens that do not expire
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Personal api token, which authenticates scripts as its user via `Authorization: Bearer`.
+///
+/// Holds the metadata only, the token itself is handed out once by [`ApiToken::create`].
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Columns that are selected to build an [`ApiToken`] via [`ApiToken::from_row`].
+const API_TOKEN_COLUMNS: &str = "id, name, scopes, created_at, expires_at";
+
+impl ApiToken {
+    /// Create an api token for a user.
+    ///
+    /// Returns the token together with the secret that authenticates requests. Only the hash of the secret is
+    /// stored, so it can not be shown again. Fails with [`ApiTokenError::InvalidApiToken`] if the name is empty
+    /// or too long, no scope is given or the expiry has already passed.
+    ///
+    /// * `user_id` - UUID of the user the token acts as
+    /// * `token` - values of the new token
+    /// * `pool` - Connection pool to use for the query
+    pub async fn create(
+        user_id: &Uuid,
+        token: &NewApiToken<'_>,
+        pool: &PgPool,
+    ) -> Result<(ApiToken, String), anyhow::Error> {
+        let name = token.name.trim();
+        if name.is_empty()
+            || name.len() > MAX_NAME_LENGTH
+            || token.scopes.is_empty()
+            || token.expires_at.is_some_and(|at| at <= Utc::now())
+        {
+            return Err(anyhow::anyhow!(ApiTokenError::InvalidApiToken));
+        }
+
+        let mut scopes: Vec<&str> = token.scopes.iter().map(Scope::as_str).collect();
+        scopes.sort_unstable();
+        scopes.dedup();
+
+        let secret = format!("{}{}", TOKEN_PREFIX, generate_token());
+        let row = sqlx::query(&format!(
+            "
+            INSERT INTO pastr.api_tokens (id, user_id, name, token_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {API_TOKEN_COLUMNS};
+            "
+        ))
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(name)
+        .bind(hash_token(&secret))
+        .bind(&scopes)
+        .bind(token.expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((Self::from_row(&row)?, secret))
+    }
+
+    /// List the api tokens of a user, newest first. Expired tokens are included.
+    ///
+    /// * `user_id` - UUID of the user
+    /// * `pool` - Connection pool to use for the query
+    pub async fn list(user_id: &Uuid, pool: &PgPool) -> Result<Vec<ApiToken>, anyhow::Error> {
+        sqlx::query(&format!(
+            "SELECT {API_TOKEN_COLUMNS} FROM pastr.api_tokens WHERE user_id = $1 ORDER BY created_at DESC;"
+        ))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(Self::from_row)
+        .collect()
+    }
+
+    /// Revoke an api token of a user.
+    ///
+    /// Returns [`ApiTokenError::ApiTokenNotFound`] if the user has no such token.
+    ///
+    /// * `user_id` - UUID of the user
+    /// * `id` - UUID of the token
+    /// * `pool` - Connection pool to use for the query
+    pub async fn delete(user_id: &Uuid, id: &Uuid, pool: &PgPool) -> Result<(), anyhow::Error> {
+        let result = sqlx::query("DELETE FROM pastr.api_tokens WHERE id = $1 AND user_id = $2;")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!(ApiTokenError::ApiTokenNotFound));
+        }
+        Ok(())
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            scopes: parse_scopes(row.try_get("scopes")?)?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+        })
+    }
+}
+
+/// Parse the scopes of a token as they are stored in the database.
+pub(super) fn parse_scopes(scopes: Vec<String>) -> Result<Vec<Scope>, anyhow::Error> {
+    scopes
+        .iter()
+        .map(|scope| Scope::try_from(scope.as_str()).map_err(|e| anyhow::anyhow!(e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_round_trip() {
+        for scope in [Scope::PasteRead, Scope::PasteWrite, Scope::PasteDelete] {
+            assert_eq!(Scope::try_from(scope.as_str()), Ok(scope));
+        }
+        assert!(Scope::try_from("paste:admin").is_err());
+    }
+}
@@ -1,10 +1,16 @@
 #![allow(unused)]
+use super::slug::is_reserved;
+use super::token::{parse_scopes, Scope};
 use crate::{
-    auth::{hash_password, verify_password_hash},
+    auth::{
+        generate_token, hash_password, hash_token, verify_password_hash, AuthError,
+        DUMMY_PASSWORD_HASH,
+    },
     routes::user,
 };
 use anyhow::Context;
-use sqlx::{PgPool, Row};
+use chrono::{Duration, Utc};
+use sqlx::{postgres::PgRow, PgPool, Row};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -12,8 +18,35 @@ use uuid::Uuid;
 pub enum UserError {
     #[error("user already exists")]
     UserAlreadyExists,
+    #[error("username is reserved")]
+    ReservedUsername,
+    #[error("user account is not activated")]
+    NotActivated,
+    #[error("activation mail was requested too recently")]
+    ActivationCooldown,
+    #[error("password reset was requested too recently")]
+    ResetCooldown,
+    #[error("invalid or expired password reset token")]
+    InvalidResetToken,
 }
 
+/// Days a login session lasts before the user has to log in again.
+pub const SESSION_DAYS: i64 = 14;
+
+/// Minutes an unactivated user has to wait before another activation mail can be requested.
+pub const ACTIVATION_COOLDOWN_MINUTES: i64 = 5;
+
+/// Minutes a password reset link stays valid.
+const RESET_MINUTES: i64 = 60;
+
+/// Minutes before another password reset can be requested for the same user.
+const RESET_COOLDOWN_MINUTES: i64 = 5;
+
+/// Columns that are selected to build a [`User`] via [`User::from_row`].
+/// The mail address is stored as `email` domain, which is selected as plain text.
+const USER_COLUMNS: &str =
+    "users.id, users.mail::text AS mail, users.username, users.password_hash, users.enabled";
+
 /// User account that is able to login to the application.
 ///
 /// Provides functionality to create new users in the database
@@ -32,13 +65,23 @@ pub struct User {
 }
 
 impl User {
+    /// UUID of the user, as it is stored as owner of pastes.
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
     /// Create the user in the database with the specified values.
     ///
     /// Used when a user trys to register for the service. A new row will be created
     /// in the DB via a transaction. Returns an error when communication with the db fails.
+    /// On success, returns the token that activates the account via [`User::activate`].
     ///
     /// * `mail`: e-mail address of the user. gets validated at the database
-    /// * `username`: username for this user. must be unique
+    /// * `username`: username for this user. must be unique and must not be a reserved word
     /// * `password`: password for this user. gets hashed before being stored
     /// * `pool`: pool to use for storage
     /// * `pepper`: pepper to use for hashing
@@ -48,7 +91,12 @@ impl User {
         password: String,
         pool: &PgPool,
         pepper: Vec<u8>,
-    ) -> Result<Uuid, anyhow::Error> {
+    ) -> Result<String, anyhow::Error> {
+        // usernames are part of the urls of vanity slugs, so they must not shadow routes
+        if is_reserved(username) {
+            return Err(anyhow::anyhow!(UserError::ReservedUsername));
+        }
+
         // check if user already exists
         let user = sqlx::query("SELECT EXISTS(SELECT 1 FROM pastr.users WHERE username = $1);")
             .bind(username)
@@ -80,84 +128,419 @@ impl User {
         .execute(&mut *tx)
         .await?;
 
-        sqlx::query("INSERT INTO pastr.users_confirmations (user_id) VALUES ($1);")
+        let token = generate_token();
+        sqlx::query("INSERT INTO pastr.users_confirmations (user_id, token_hash) VALUES ($1, $2);")
             .bind(id)
+            .bind(hash_token(&token))
             .execute(&mut *tx)
             .await?;
 
         tx.commit().await?;
-        Ok(id)
+        Ok(token)
     }
 
-    /// Activate a given user, allowing him to log in to the application.
+    /// Activate a user, allowing them to log in to the application.
     ///
-    /// Updates the user with the given `id` to be enabled. Requires that a row with the same id
-    /// exists in `users_confirmations`. Function returns an error if there is no signup request present.
+    /// Looks up the signup request the token belongs to and consumes it, so every token works only once.
+    /// Returns an error if there is no such request or it is older than 2 days.
     ///
-    /// * `id` - UUID of the user to activate
+    /// * `token` - activation token from the mail, as returned by [`User::create`]
     /// * `pool` - Connection pool to use for the queries
-    pub async fn activate(id: &Uuid, pool: &PgPool) -> Result<(), anyhow::Error> {
-        // check if there is a valid request for the given user
-        let signup_exists = sqlx::query(
+    pub async fn activate(token: &str, pool: &PgPool) -> Result<(), anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        let id = sqlx::query(
+            "
+            DELETE FROM pastr.users_confirmations
+            WHERE token_hash = $1 AND created_at > NOW() - INTERVAL '2 days'
+            RETURNING user_id;
+            ",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.try_get::<Uuid, &str>("user_id"))
+        .transpose()?
+        .ok_or_else(|| anyhow::anyhow!("no signup request exists for given token"))?;
+
+        sqlx::query("UPDATE pastr.users SET enabled = true WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Check the credentials of a user.
+    ///
+    /// Returns the user if `password` matches the stored hash, or [`AuthError::InvalidCredentials`]
+    /// if there is no such user or the password is wrong. Users that have not activated their account
+    /// yet get [`UserError::NotActivated`], which is only checked after the password, so it does not
+    /// reveal anything to others.
+    ///
+    /// * `username` - name the user registered with
+    /// * `password` - password to check
+    /// * `pool` - Connection pool to use for the query
+    /// * `pepper` - pepper that was used for hashing
+    pub async fn login(
+        username: &str,
+        password: &str,
+        pool: &PgPool,
+        pepper: Vec<u8>,
+    ) -> Result<User, anyhow::Error> {
+        let user = sqlx::query(&format!(
+            "SELECT {USER_COLUMNS} FROM pastr.users WHERE username = $1;"
+        ))
+        .bind(username)
+        .fetch_optional(pool)
+        .await
+        .context("failed to retrieve user")?
+        .map(|row| Self::from_row(&row))
+        .transpose()?;
+
+        // unknown users are verified as well, so the response time does not tell which users exist
+        let hash = user
+            .as_ref()
+            .map_or(DUMMY_PASSWORD_HASH, |user| &user.password_hash)
+            .to_owned();
+        let password = password.to_owned();
+        actix_web::rt::task::spawn_blocking(move || {
+            verify_password_hash(&password, &hash, pepper.as_slice())
+        })
+        .await??;
+
+        let Some(user) = user else {
+            return Err(AuthError::InvalidCredentials(anyhow::anyhow!("unknown user")).into());
+        };
+
+        if !user.enabled {
+            return Err(anyhow::anyhow!(UserError::NotActivated));
+        }
+        Ok(user)
+    }
+
+    /// Replace the signup request of an unactivated user with a fresh one, so a new activation mail can be sent.
+    ///
+    /// Returns the new activation token, or `None` if there is no unactivated user with this mail address.
+    /// The token of the previous request stops working.
+    /// Fails with [`UserError::ActivationCooldown`] if the last request is younger than
+    /// [`ACTIVATION_COOLDOWN_MINUTES`], so the mail can not be used to flood someone's inbox.
+    ///
+    /// * `mail` - mail address the user registered with
+    /// * `pool` - Connection pool to use for the queries
+    pub async fn renew_confirmation(
+        mail: &str,
+        pool: &PgPool,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        let id = sqlx::query(
+            "SELECT id FROM pastr.users WHERE mail = $1::citext AND enabled IS NOT TRUE FOR UPDATE;",
+        )
+        .bind(mail)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.try_get::<Uuid, &str>("id"))
+        .transpose()?;
+
+        let Some(id) = id else {
+            return Ok(None);
+        };
+
+        let recent = sqlx::query(
             "SELECT EXISTS(
-                SELECT * FROM pastr.users_confirmations
-                WHERE user_id = $1
+                SELECT 1 FROM pastr.users_confirmations
+                WHERE user_id = $1 AND created_at > NOW() - make_interval(mins => $2)
             );",
         )
         .bind(id)
-        .fetch_one(pool)
+        .bind(ACTIVATION_COOLDOWN_MINUTES as i32)
+        .fetch_one(&mut *tx)
         .await?
         .try_get::<bool, &str>("exists")?;
 
-        // enable the user if there was a valid request and delete it.
-        if signup_exists {
-            let mut tx = pool.begin().await?;
-            sqlx::query(
-                "
-                UPDATE pastr.users
-                SET enabled = true
-                WHERE id = $1;
-                ",
-            )
+        if recent {
+            return Err(anyhow::anyhow!(UserError::ActivationCooldown));
+        }
+
+        sqlx::query("DELETE FROM pastr.users_confirmations WHERE user_id = $1;")
             .bind(id)
             .execute(&mut *tx)
             .await?;
-
-            sqlx::query(
-                "
-                DELETE FROM pastr.users_confirmations
-                WHERE user_id = $1
-                ",
-            )
+        let token = generate_token();
+        sqlx::query("INSERT INTO pastr.users_confirmations (user_id, token_hash) VALUES ($1, $2);")
             .bind(id)
+            .bind(hash_token(&token))
             .execute(&mut *tx)
             .await?;
 
-            tx.commit().await?;
-            Ok(())
-        } else {
-            // return an error if there was no request
-            Err(anyhow::anyhow!(
-                "no signup request exists for given user id"
-            ))
+        tx.commit().await?;
+        Ok(Some(token))
+    }
+
+    /// Lift the cooldown of a renewed signup request whose activation mail could not be sent.
+    ///
+    /// The previous token stopped working when the request was renewed, so the user has to be able to ask for
+    /// another mail right away. The request is backdated by [`ACTIVATION_COOLDOWN_MINUTES`], which expires it
+    /// that much earlier as well.
+    ///
+    /// * `token` - activation token returned by [`User::renew_confirmation`]
+    /// * `pool` - Connection pool to use for the query
+    pub async fn release_activation_cooldown(
+        token: &str,
+        pool: &PgPool,
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "
+            UPDATE pastr.users_confirmations SET created_at = created_at - make_interval(mins => $2)
+            WHERE token_hash = $1;
+            ",
+        )
+        .bind(hash_token(token))
+        .bind(ACTIVATION_COOLDOWN_MINUTES as i32)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Start a password reset for the activated user with the given mail address.
+    ///
+    /// Returns the token for the reset link, which is valid for [`RESET_MINUTES`] and replaces any previous one,
+    /// or `None` if there is no such user. Fails with [`UserError::ResetCooldown`] if the last reset was requested
+    /// less than [`RESET_COOLDOWN_MINUTES`] ago, like [`User::renew_confirmation`] does for activation mails.
+    ///
+    /// * `mail` - mail address the user registered with
+    /// * `pool` - Connection pool to use for the queries
+    pub async fn request_password_reset(
+        mail: &str,
+        pool: &PgPool,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let mut tx = pool.begin().await?;
+
+        let id = sqlx::query(
+            "SELECT id FROM pastr.users WHERE mail = $1::citext AND enabled FOR UPDATE;",
+        )
+        .bind(mail)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.try_get::<Uuid, &str>("id"))
+        .transpose()?;
+
+        let Some(id) = id else {
+            return Ok(None);
+        };
+
+        let recent = sqlx::query(
+            "SELECT EXISTS(
+                SELECT 1 FROM pastr.password_resets
+                WHERE user_id = $1 AND created_at > NOW() - make_interval(mins => $2)
+            );",
+        )
+        .bind(id)
+        .bind(RESET_COOLDOWN_MINUTES as i32)
+        .fetch_one(&mut *tx)
+        .await?
+        .try_get::<bool, &str>("exists")?;
+
+        if recent {
+            return Err(anyhow::anyhow!(UserError::ResetCooldown));
         }
+
+        let token = generate_token();
+        sqlx::query(
+            "
+            INSERT INTO pastr.password_resets (token_hash, user_id, expires_at) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET token_hash = EXCLUDED.token_hash, created_at = NOW(), expires_at = EXCLUDED.expires_at;
+            ",
+        )
+        .bind(hash_token(&token))
+        .bind(id)
+        .bind(Utc::now() + Duration::minutes(RESET_MINUTES))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(token))
     }
 
-    pub async fn login(
-        username: &str,
-        password: &str,
+    /// Lift the cooldown of a password reset whose mail could not be sent.
+    ///
+    /// The previous reset link stopped working when the reset was requested, so the user has to be able to ask
+    /// for another one right away. The reset is backdated by [`RESET_COOLDOWN_MINUTES`], its link stays valid.
+    ///
+    /// * `token` - reset token returned by [`User::request_password_reset`]
+    /// * `pool` - Connection pool to use for the query
+    pub async fn release_reset_cooldown(token: &str, pool: &PgPool) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "
+            UPDATE pastr.password_resets SET created_at = created_at - make_interval(mins => $2)
+            WHERE token_hash = $1;
+            ",
+        )
+        .bind(hash_token(token))
+        .bind(RESET_COOLDOWN_MINUTES as i32)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Set a new password with the token of a password reset, end all sessions of the user and revoke
+    /// their api tokens.
+    ///
+    /// The token is consumed, so it works only once. Fails with [`UserError::InvalidResetToken`]
+    /// if there is no such reset or it has expired.
+    ///
+    /// * `token` - token returned by [`User::request_password_reset`]
+    /// * `password` - new password. gets hashed before being stored
+    /// * `pool` - Connection pool to use for the queries
+    /// * `pepper` - pepper to use for hashing
+    pub async fn reset_password(
+        token: &str,
+        password: String,
         pool: &PgPool,
         pepper: Vec<u8>,
     ) -> Result<(), anyhow::Error> {
-        let password_hash =
-            sqlx::query("SELECT password_hash FROM pastr.users WHERE username = $1;")
-                .bind(username)
-                .fetch_one(pool)
-                .await
-                .context("failed to retrieve password hash for user")?
-                .try_get::<String, &str>("password_hash")?;
-
-        verify_password_hash(password, password_hash.as_str(), pepper.as_slice())
-            .context("passwords do not match")
+        // hash before the transaction, so it does not hold the reset row locked meanwhile
+        let hash = actix_web::rt::task::spawn_blocking(move || {
+            hash_password(password.as_str(), pepper.as_slice())
+        })
+        .await??;
+
+        let mut tx = pool.begin().await?;
+
+        let id = sqlx::query(
+            "DELETE FROM pastr.password_resets WHERE token_hash = $1 AND expires_at > NOW() RETURNING user_id;",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.try_get::<Uuid, &str>("user_id"))
+        .transpose()?
+        .ok_or_else(|| anyhow::anyhow!(UserError::InvalidResetToken))?;
+
+        sqlx::query("UPDATE pastr.users SET password_hash = $2 WHERE id = $1;")
+            .bind(id)
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+
+        // whoever knew the old password must not stay logged in, nor keep the tokens they could create with it
+        sqlx::query("DELETE FROM pastr.sessions WHERE user_id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM pastr.api_tokens WHERE user_id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Start a login session for the user, which lasts [`SESSION_DAYS`].
+    ///
+    /// Returns the token that identifies the session. Only its hash is stored, so it can not be retrieved again.
+    ///
+    /// * `pool` - Connection pool to use for the query
+    pub async fn start_session(&self, pool: &PgPool) -> Result<String, anyhow::Error> {
+        let token = generate_token();
+
+        sqlx::query(
+            "INSERT INTO pastr.sessions (token_hash, user_id, expires_at) VALUES ($1, $2, $3);",
+        )
+        .bind(hash_token(&token))
+        .bind(self.id)
+        .bind(Utc::now() + Duration::days(SESSION_DAYS))
+        .execute(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Find the user a session belongs to. Returns `None` if the session does not exist or has expired.
+    ///
+    /// * `token` - token returned by [`User::start_session`]
+    /// * `pool` - Connection pool to use for the query
+    pub async fn from_session(token: &str, pool: &PgPool) -> Result<Option<User>, anyhow::Error> {
+        let user = sqlx::query(&format!(
+            "
+            SELECT {USER_COLUMNS} FROM pastr.sessions
+            JOIN pastr.users ON users.id = sessions.user_id
+            WHERE sessions.token_hash = $1 AND sessions.expires_at > NOW();
+            "
+        ))
+        .bind(hash_token(token))
+        .fetch_optional(pool)
+        .await?
+        .map(|row| Self::from_row(&row))
+        .transpose()?;
+        Ok(user)
+    }
+
+    /// Find the user an api token belongs to, together with the scopes of the token.
+    ///
+    /// Returns `None` if the token does not exist or has expired.
+    ///
+    /// * `token` - secret returned by [`ApiToken::create`](super::ApiToken::create)
+    /// * `pool` - Connection pool to use for the query
+    pub async fn from_api_token(
+        token: &str,
+        pool: &PgPool,
+    ) -> Result<Option<(User, Vec<Scope>)>, anyhow::Error> {
+        let row = sqlx::query(&format!(
+            "
+            SELECT {USER_COLUMNS}, api_tokens.scopes FROM pastr.api_tokens
+            JOIN pastr.users ON users.id = api_tokens.user_id
+            WHERE api_tokens.token_hash = $1
+                AND (api_tokens.expires_at IS NULL OR api_tokens.expires_at > NOW());
+            "
+        ))
+        .bind(hash_token(token))
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let scopes = parse_scopes(row.try_get("scopes")?)?;
+        Ok(Some((Self::from_row(&row)?, scopes)))
+    }
+
+    /// End a session, e.g. when the user logs out. Unknown sessions are ignored.
+    ///
+    /// * `token` - token returned by [`User::start_session`]
+    /// * `pool` - Connection pool to use for the query
+    pub async fn end_session(token: &str, pool: &PgPool) -> Result<(), anyhow::Error> {
+        sqlx::query("DELETE FROM pastr.sessions WHERE token_hash = $1;")
+            .bind(hash_token(token))
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete all sessions whose expiration date has passed. Returns the number of deleted sessions.
+    ///
+    /// * `pool` - Connection pool to use for the query
+    pub async fn delete_expired_sessions(pool: &PgPool) -> Result<u64, anyhow::Error> {
+        let result = sqlx::query("DELETE FROM pastr.sessions WHERE expires_at <= NOW();")
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            mail: row.try_get("mail")?,
+            username: row.try_get("username")?,
+            password_hash: row.try_get("password_hash")?,
+            enabled: row
+                .try_get::<Option<bool>, &str>("enabled")?
+                .unwrap_or(false),
+        })
     }
 }
@@ -0,0 +1,176 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Theme used for highlighting. Matches the dark bootstrap theme of the pages.
+const THEME: &str = "base16-ocean.dark";
+
+/// Pastes larger than this are rendered as plain text, since highlighting them takes too long.
+const MAX_HIGHLIGHT_SIZE: usize = 512 * 1024;
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEMES: OnceLock<Theme> = OnceLock::new();
+    THEMES.get_or_init(|| {
+        ThemeSet::load_defaults()
+            .themes
+            .remove(THEME)
+            .expect("default theme is missing")
+    })
+}
+
+/// Determine the syntax used to highlight a paste.
+///
+/// The explicitly set `language` takes precedence. It may be the name of a language (`Rust`) or one
+/// of its file extensions (`rs`). Otherwise the extension of `filename` is used. Falls back to plain text.
+///
+/// * `language` - language set by the creator of the paste
+/// * `filename` - filename of the paste, e.g. its title
+pub fn find_syntax(language: Option<&str>, filename: Option<&str>) -> &'static SyntaxReference {
+    let syntaxes = syntax_set();
+
+    language
+        .and_then(|language| syntaxes.find_syntax_by_token(language.trim()))
+        .or_else(|| {
+            filename
+                .and_then(|filename| Path::new(filename).extension())
+                .and_then(|extension| extension.to_str())
+                .and_then(|extension| syntaxes.find_syntax_by_extension(extension))
+        })
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+/// Check whether `language` is the name or a file extension of a language that can be highlighted.
+pub fn is_known_language(language: &str) -> bool {
+    syntax_set().find_syntax_by_token(language).is_some()
+}
+
+/// Render `content` as highlighted html, returning one fragment per line.
+///
+/// The fragments only contain inline styled `span` elements and no line breaks,
+/// so they can be wrapped with line numbers and anchors by the caller.
+///
+/// * `content` - text to highlight
+/// * `syntax` - syntax to use, see [`find_syntax`]
+pub fn highlight_lines(content: &str, syntax: &SyntaxReference) -> Vec<String> {
+    let syntaxes = syntax_set();
+    let syntax = if content.len() > MAX_HIGHLIGHT_SIZE {
+        syntaxes.find_syntax_plain_text()
+    } else {
+        syntax
+    };
+
+    let mut highlighter = HighlightLines::new(syntax, theme());
+    let mut failed = false;
+
+    LinesWithEndings::from(content)
+        .map(|line| {
+            if !failed {
+                let html = highlighter
+                    .highlight_line(line, syntaxes)
+                    .and_then(|regions| {
+                        // the line ending is part of the last region, the caller handles line breaks
+                        let regions: Vec<_> = regions
+                            .into_iter()
+                            .map(|(style, text)| (style, text.trim_end_matches(['\r', '\n'])))
+                            .collect();
+                        styled_line_to_highlighted_html(&regions, IncludeBackground::No)
+                    });
+                match html {
+                    Ok(html) => return html,
+                    Err(e) => {
+                        // the highlighter state is unusable after an error, render the rest as plain text
+                        tracing::warn!("failed to highlight paste: {}", e);
+                        failed = true;
+                    }
+                }
+            }
+            escape_html(line.trim_end_matches(['\r', '\n']))
+        })
+        .collect()
+}
+
+/// Parse a range of line numbers as used in links to a paste, e.g. `L10-L20`, `10-20` or `L10`.
+///
+/// Returns `None` if the range is malformed or starts at line 0.
+pub fn parse_line_range(range: &str) -> Option<RangeInclusive<usize>> {
+    let number = |s: &str| s.trim().trim_start_matches('L').parse::<usize>().ok();
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (number(start)?, number(end)?),
+        None => {
+            let line = number(range)?;
+            (line, line)
+        }
+    };
+
+    if start == 0 {
+        return None;
+    }
+    Some(start.min(end)..=start.max(end))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syntax_from_language() {
+        assert_eq!(find_syntax(Some("rust"), None).name, "Rust");
+        assert_eq!(find_syntax(Some("py"), Some("main.rs")).name, "Python");
+    }
+
+    #[test]
+    fn syntax_from_filename() {
+        assert_eq!(find_syntax(None, Some("main.rs")).name, "Rust");
+        assert_eq!(find_syntax(Some("unknown"), Some("main.rs")).name, "Rust");
+    }
+
+    #[test]
+    fn syntax_falls_back_to_plain_text() {
+        assert_eq!(find_syntax(None, None).name, "Plain Text");
+        assert_eq!(find_syntax(None, Some("README")).name, "Plain Text");
+    }
+
+    #[test]
+    fn one_fragment_per_line() {
+        let lines = highlight_lines("fn main() {\n    <b>\n}\n", find_syntax(Some("rs"), None));
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| !line.contains('\n')));
+        assert!(lines[1].contains("&lt;b&gt;"));
+    }
+
+    #[test]
+    fn line_ranges() {
+        assert_eq!(parse_line_range("L10-L20"), Some(10..=20));
+        assert_eq!(parse_line_range("20-10"), Some(10..=20));
+        assert_eq!(parse_line_range("L7"), Some(7..=7));
+        assert_eq!(parse_line_range("L0"), None);
+        assert_eq!(parse_line_range("Lx-L2"), None);
+    }
+}
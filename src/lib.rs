@@ -1,7 +1,15 @@
+pub mod archive;
 pub mod auth;
 pub mod config;
+pub mod detect;
+pub mod diff;
 pub mod entity;
+pub mod highlight;
 pub mod log;
 pub mod mail;
+pub mod reaper;
 pub mod routes;
 pub mod setup;
+pub mod sniff;
+pub mod storage;
+pub mod tcp;
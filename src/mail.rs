@@ -1,12 +1,18 @@
 use askama::Template;
 use sendgrid::v3::{Content, Email, Message, Personalization, Sender};
-use uuid::Uuid;
 
 #[derive(Template, Debug)]
 #[template(path = "mail.html")]
 #[allow(unused)]
 struct SignUpTemplate<'a> {
-    user_id: &'a Uuid,
+    token: &'a str,
+    base_url: &'a str,
+}
+
+#[derive(Template, Debug)]
+#[template(path = "reset_mail.html")]
+struct PasswordResetTemplate<'a> {
+    token: &'a str,
     base_url: &'a str,
 }
 
@@ -15,23 +21,53 @@ struct SignUpTemplate<'a> {
 /// Uses the [Sendgrid](https://sendgrid.com/) API to send a confirmation e-mail in order to register.
 /// Requires that a valid sendgrid api key is supplied in the config file.
 ///
-/// * `user_id` - uuid of the user that should get activated with this mail
+/// * `token` - activation token of the user, as returned by [`User::create`][u]
 /// * `mail` - mail destination
 /// * `base_url` - base url of the service, used to construct correct links in the mail
 /// * `api_key` - sendgrid api key
+///
+/// [u]: crate::entity::User::create
 pub async fn send_registration_mail(
-    user_id: &Uuid,
-    mail: &String,
-    base_url: &String,
-    api_key: &String,
+    token: &str,
+    mail: &str,
+    base_url: &str,
+    api_key: &str,
 ) -> Result<(), anyhow::Error> {
-    let p = Personalization::new(Email::new(mail));
-    let template = SignUpTemplate { user_id, base_url };
+    let template = SignUpTemplate { token, base_url };
+    send_mail(mail, "Pastr Registration", template.render()?, api_key).await
+}
+
+/// Send a mail with a link to reset the password of an account.
+///
+/// Uses the same [Sendgrid](https://sendgrid.com/) setup as [`send_registration_mail`].
+///
+/// * `token` - reset token, as returned by [`User::request_password_reset`][u]
+/// * `mail` - mail destination
+/// * `base_url` - base url of the service, used to construct correct links in the mail
+/// * `api_key` - sendgrid api key
+///
+/// [u]: crate::entity::User::request_password_reset
+pub async fn send_password_reset_mail(
+    token: &str,
+    mail: &str,
+    base_url: &str,
+    api_key: &str,
+) -> Result<(), anyhow::Error> {
+    let template = PasswordResetTemplate { token, base_url };
+    send_mail(mail, "Pastr Password Reset", template.render()?, api_key).await
+}
 
-    let mail_html = template.render()?;
+/// Send an html mail via the Sendgrid API.
+async fn send_mail(
+    mail: &str,
+    subject: &str,
+    mail_html: String,
+    api_key: &str,
+) -> Result<(), anyhow::Error> {
+    let p = Personalization::new(Email::new(mail));
 
     let msg = Message::new(Email::new("pastr@1ux.dev"))
-        .set_subject("Pastr Registration")
+        .set_subject(subject)
         .add_content(
             Content::new()
                 .set_content_type("text/html")
@@ -6,6 +6,9 @@ async fn main() -> anyhow::Result<()> {
     let cfg = config::get_config().unwrap();
     let app = Application::with_config(cfg).await?;
     println!("Running Application on 127.0.0.1:{}", app.port);
+    if let Some(tcp_port) = app.tcp_port {
+        println!("Accepting tcp pastes on 127.0.0.1:{}", tcp_port);
+    }
     let app_task = tokio::spawn(app.run());
     let _ = app_task.await?;
     Ok(())
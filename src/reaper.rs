@@ -0,0 +1,33 @@
+use crate::entity::{Paste, User};
+use crate::storage::BlobStore;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+/// Periodically delete pastes and login sessions whose expiration date has passed.
+///
+/// Runs forever, so it should be spawned as a separate task.
+/// Errors are logged and the deletion is retried on the next tick.
+///
+/// * `pool` - [`PgPool`] the pastes are stored in
+/// * `store` - [`BlobStore`] holding the content of the pastes
+/// * `interval` - time between two runs
+pub async fn reap_expired_pastes(pool: PgPool, store: Arc<dyn BlobStore>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        match Paste::delete_expired(&pool, store.as_ref()).await {
+            Ok(0) => {}
+            Ok(deleted) => tracing::info!("deleted {} expired pastes", deleted),
+            Err(e) => tracing::error!("failed to delete expired pastes: {:?}", e),
+        }
+        match User::delete_expired_sessions(&pool).await {
+            Ok(0) => {}
+            Ok(deleted) => tracing::info!("deleted {} expired sessions", deleted),
+            Err(e) => tracing::error!("failed to delete expired sessions: {:?}", e),
+        }
+    }
+}
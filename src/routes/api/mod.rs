@@ -1,5 +1,6 @@
 use actix_web::{error, HttpRequest, HttpResponse};
 
+pub mod paste;
 pub mod user;
 
 #[derive(Debug, Clone, serde::Serialize, PartialEq)]
@@ -17,14 +18,203 @@ impl<'a> ApiErrorMessage<'a> {
             field: "username",
         }
     }
+
+    pub fn paste_not_found() -> Self {
+        Self {
+            message: "paste not found",
+            code: 2,
+            field: "id",
+        }
+    }
+
+    pub fn empty_content() -> Self {
+        Self {
+            message: "paste content must not be empty",
+            code: 3,
+            field: "content",
+        }
+    }
+
+    pub fn invalid_edit_token() -> Self {
+        Self {
+            message: "missing or invalid edit token",
+            code: 4,
+            field: "edit_token",
+        }
+    }
+
+    pub fn paste_too_large() -> Self {
+        Self {
+            message: "paste content exceeds the maximum size",
+            code: 5,
+            field: "content",
+        }
+    }
+
+    pub fn private_requires_owner() -> Self {
+        Self {
+            message: "only logged in users can create private pastes",
+            code: 6,
+            field: "visibility",
+        }
+    }
+
+    pub fn revision_not_found() -> Self {
+        Self {
+            message: "paste revision not found",
+            code: 7,
+            field: "revision",
+        }
+    }
+
+    pub fn invalid_files() -> Self {
+        Self {
+            message: "pass either content or one or more files with distinct names without slashes",
+            code: 8,
+            field: "files",
+        }
+    }
+
+    pub fn attachment_not_found() -> Self {
+        Self {
+            message: "attachment not found",
+            code: 9,
+            field: "filename",
+        }
+    }
+
+    pub fn invalid_attachment() -> Self {
+        Self {
+            message: "attachments need content and a distinct name without slashes, burn-after-reading pastes can not have any",
+            code: 10,
+            field: "filename",
+        }
+    }
+
+    pub fn attachment_too_large() -> Self {
+        Self {
+            message: "attachment exceeds the maximum size",
+            code: 11,
+            field: "attachment",
+        }
+    }
+
+    pub fn invalid_paste_password() -> Self {
+        Self {
+            message: "missing or invalid paste password",
+            code: 12,
+            field: "password",
+        }
+    }
+
+    pub fn invalid_encrypted_paste() -> Self {
+        Self {
+            message:
+                "encrypted pastes consist of a single unnamed file of base64 encoded ciphertext",
+            code: 13,
+            field: "content",
+        }
+    }
+
+    pub fn reserved_username() -> Self {
+        Self {
+            message: "username is reserved for a route of the application",
+            code: 14,
+            field: "username",
+        }
+    }
+
+    pub fn invalid_slug() -> Self {
+        Self {
+            message:
+                "slugs need a logged in owner and consist of lowercase words that are not reserved",
+            code: 15,
+            field: "slug",
+        }
+    }
+
+    pub fn slug_taken() -> Self {
+        Self {
+            message: "slug is already taken",
+            code: 16,
+            field: "slug",
+        }
+    }
+
+    pub fn invalid_credentials() -> Self {
+        Self {
+            message: "invalid username or password",
+            code: 17,
+            field: "password",
+        }
+    }
+
+    pub fn account_not_activated() -> Self {
+        Self {
+            message:
+                "the account has not been activated yet, follow the link in the activation mail",
+            code: 18,
+            field: "username",
+        }
+    }
+
+    pub fn invalid_reset_token() -> Self {
+        Self {
+            message: "the password reset link is invalid or has expired, request a new one",
+            code: 19,
+            field: "token",
+        }
+    }
+
+    pub fn empty_password() -> Self {
+        Self {
+            message: "password must not be empty",
+            code: 20,
+            field: "password",
+        }
+    }
+
+    pub fn missing_scope() -> Self {
+        Self {
+            message: "the api token lacks the scope required by this request",
+            code: 21,
+            field: "scopes",
+        }
+    }
+
+    pub fn invalid_api_token() -> Self {
+        Self {
+            message: "api tokens need a name, at least one scope of paste:read, paste:write or paste:delete and an expiry in the future",
+            code: 22,
+            field: "scopes",
+        }
+    }
+
+    pub fn api_token_not_found() -> Self {
+        Self {
+            message: "api token not found",
+            code: 23,
+            field: "id",
+        }
+    }
+
+    pub fn session_required() -> Self {
+        Self {
+            message: "api tokens can only be managed while logged in, not with another api token",
+            code: 24,
+            field: "authorization",
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, PartialEq)]
-pub struct ApiResponse<'a> {
+pub struct ApiResponse<'a, T = ()> {
     success: bool,
     message: &'a str,
     #[serde(skip_serializing_if = "Option::is_none")]
     errors: Option<Vec<ApiErrorMessage<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<T>,
 }
 
 impl<'a> ApiResponse<'a> {
@@ -33,13 +223,7 @@ impl<'a> ApiResponse<'a> {
             success,
             message,
             errors: None,
-        }
-    }
-
-    pub fn add_error(&mut self, error: ApiErrorMessage<'a>) {
-        match self.errors.take() {
-            None => self.errors = Some(vec![error]),
-            Some(mut v) => v.push(error),
+            data: None,
         }
     }
 
@@ -48,6 +232,26 @@ impl<'a> ApiResponse<'a> {
             success,
             message,
             errors: Some(errors),
+            data: None,
+        }
+    }
+}
+
+impl<'a, T> ApiResponse<'a, T> {
+    /// Create a successful response that carries `data` as payload.
+    pub fn with_data(message: &'a str, data: T) -> Self {
+        Self {
+            success: true,
+            message,
+            errors: None,
+            data: Some(data),
+        }
+    }
+
+    pub fn add_error(&mut self, error: ApiErrorMessage<'a>) {
+        match self.errors.take() {
+            None => self.errors = Some(vec![error]),
+            Some(mut v) => v.push(error),
         }
     }
 }
@@ -85,7 +289,8 @@ mod tests {
             ApiResponse {
                 success: false,
                 message: "test error",
-                errors: Some(vec![ApiErrorMessage::user_already_exists()])
+                errors: Some(vec![ApiErrorMessage::user_already_exists()]),
+                data: None,
             }
         )
     }
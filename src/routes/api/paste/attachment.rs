@@ -0,0 +1,95 @@
+use crate::entity::{Attachment, NewAttachment, Scope};
+use crate::routes::api::{ApiErrorMessage, ApiResponse};
+use crate::routes::session::OptionalUser;
+use crate::setup::MaxAttachmentSize;
+use crate::storage::BlobStore;
+use actix_web::web::{self, BytesMut};
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use sqlx::PgPool;
+
+use super::{editor, paste_error_response, AttachmentData};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AttachmentQuery {
+    /// Name of the new attachment
+    filename: String,
+}
+
+/// Attach the request body as binary file to a paste.
+///
+/// The media type is detected from the content, whatever the `Content-Type` of the request claims.
+#[tracing::instrument(
+    name = "Attachment Creation Request",
+    skip(req, user, payload, pool, store, max_size)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_attachment(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    query: web::Query<AttachmentQuery>,
+    mut payload: web::Payload,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    max_size: web::Data<MaxAttachmentSize>,
+) -> HttpResponse {
+    let editor = match editor(&req, user.as_ref(), Scope::PasteWrite) {
+        Ok(editor) => editor,
+        Err(response) => return response,
+    };
+
+    let mut data = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::debug!("failed to read attachment: {:?}", e);
+                return HttpResponse::BadRequest()
+                    .json(ApiResponse::new(false, "malformed request body"));
+            }
+        };
+        if data.len() + chunk.len() > max_size.0 {
+            return HttpResponse::PayloadTooLarge().json(ApiResponse::with_errors(
+                false,
+                "invalid attachment",
+                vec![ApiErrorMessage::attachment_too_large()],
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let attachment = NewAttachment {
+        filename: &query.filename,
+        data: &data,
+    };
+
+    match Attachment::add(&short_id, editor, &attachment, &pool, store.get_ref()).await {
+        Ok(attachment) => HttpResponse::Created().json(ApiResponse::with_data(
+            "attachment added",
+            AttachmentData::from(attachment),
+        )),
+        Err(e) => paste_error_response(e),
+    }
+}
+
+/// Remove an attachment from a paste.
+#[tracing::instrument(name = "Attachment Deletion Request", skip(req, user, pool, store))]
+pub async fn delete_attachment(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+) -> HttpResponse {
+    let (short_id, filename) = path.into_inner();
+    let editor = match editor(&req, user.as_ref(), Scope::PasteWrite) {
+        Ok(editor) => editor,
+        Err(response) => return response,
+    };
+
+    match Attachment::delete(&short_id, editor, &filename, &pool, store.get_ref()).await {
+        Ok(()) => HttpResponse::Ok().json(ApiResponse::new(true, "attachment deleted")),
+        Err(e) => paste_error_response(e),
+    }
+}
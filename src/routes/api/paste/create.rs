@@ -0,0 +1,117 @@
+use crate::entity::{Expiry, NewFile, NewPaste, Paste, PasteKind, Scope, Visibility};
+use crate::routes::api::ApiResponse;
+use crate::routes::session::OptionalUser;
+use crate::setup::{MaxExpiry, MaxPasteSize, Pepper};
+use crate::storage::BlobStore;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use super::{
+    check_files, check_scope, invalid_files_response, paste_error_response, CreatedPasteData,
+    FileForm,
+};
+
+#[derive(serde::Deserialize)]
+pub struct PasteForm {
+    title: Option<String>,
+    /// Content of a paste with a single file. Can not be combined with `files`
+    content: Option<String>,
+    /// Language of the single file given by `content`
+    language: Option<String>,
+    /// Files of the paste, in the order they are shown
+    files: Option<Vec<FileForm>>,
+    /// Requested lifetime. Capped by the configured maximum
+    expires: Option<Expiry>,
+    /// Delete the paste the first time it is read
+    #[serde(default)]
+    burn_after_reading: bool,
+    /// Only logged in users may create private pastes
+    #[serde(default)]
+    visibility: Visibility,
+    /// Password viewers have to enter to see the paste
+    password: Option<String>,
+    /// Encrypted pastes hold a single file of ciphertext produced by the client
+    #[serde(default)]
+    kind: PasteKind,
+    /// Vanity slug that makes the paste reachable as `/{username}/{slug}`. Only for logged in users
+    slug: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Paste Creation Request",
+    skip(user, pool, store, form, max_size, max_expiry, pepper)
+)]
+pub async fn create_paste(
+    OptionalUser(user): OptionalUser,
+    form: web::Json<PasteForm>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    max_size: web::Data<MaxPasteSize>,
+    max_expiry: web::Data<MaxExpiry>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    if let Some(response) = check_scope(user.as_ref(), Scope::PasteWrite) {
+        return response;
+    }
+
+    let PasteForm {
+        title,
+        content,
+        language,
+        files,
+        expires,
+        burn_after_reading,
+        visibility,
+        password,
+        kind,
+        slug,
+    } = form.0;
+
+    let files: Vec<NewFile> = match (&content, &files) {
+        (Some(content), None) => vec![NewFile {
+            filename: None,
+            content,
+            language: language.as_deref(),
+        }],
+        (None, Some(files)) => files.iter().map(NewFile::from).collect(),
+        _ => return invalid_files_response(),
+    };
+
+    if let Some(response) = check_files(&files, max_size.0) {
+        return response;
+    }
+
+    let password_hash = match password.filter(|password| !password.is_empty()) {
+        Some(password) => {
+            let pepper = pepper.0.expose_secret().as_bytes().to_vec();
+            match Paste::hash_password(&password, pepper).await {
+                Ok(hash) => Some(hash),
+                Err(e) => return paste_error_response(e),
+            }
+        }
+        None => None,
+    };
+
+    let paste = NewPaste {
+        owner: user.as_ref().map(|user| *user.id()),
+        title: title.as_deref(),
+        files,
+        attachments: Vec::new(),
+        expires_at: max_expiry.cap(expires).expires_at(Utc::now()),
+        burn_after_reading,
+        visibility,
+        password_hash,
+        kind,
+        slug: slug.as_deref(),
+    };
+
+    match Paste::create(&paste, &pool, store.get_ref()).await {
+        Ok(created) => HttpResponse::Created().json(ApiResponse::with_data(
+            "paste created",
+            CreatedPasteData::from(created),
+        )),
+        Err(e) => paste_error_response(e),
+    }
+}
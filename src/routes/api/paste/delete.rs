@@ -0,0 +1,27 @@
+use crate::entity::{Paste, Scope};
+use crate::routes::api::ApiResponse;
+use crate::routes::session::OptionalUser;
+use crate::storage::BlobStore;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+use super::{editor, paste_error_response};
+
+#[tracing::instrument(name = "Paste Deletion Request", skip(req, user, pool, store))]
+pub async fn delete_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+) -> HttpResponse {
+    let editor = match editor(&req, user.as_ref(), Scope::PasteDelete) {
+        Ok(editor) => editor,
+        Err(response) => return response,
+    };
+
+    match Paste::delete(&short_id, editor, &pool, store.get_ref()).await {
+        Ok(_) => HttpResponse::Ok().json(ApiResponse::new(true, "paste deleted")),
+        Err(e) => paste_error_response(e),
+    }
+}
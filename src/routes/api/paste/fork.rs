@@ -0,0 +1,102 @@
+use crate::entity::{Expiry, NewFork, Paste, Scope, Visibility};
+use crate::routes::api::ApiResponse;
+use crate::routes::paste::is_unlocked;
+use crate::routes::session::{CurrentUser, OptionalUser};
+use crate::setup::{MaxExpiry, Pepper};
+use crate::storage::BlobStore;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+
+use super::{
+    check_scope, locked_paste_response, paste_error_response, CreatedPasteData, PasteSummary,
+};
+
+#[derive(Default, serde::Deserialize)]
+pub struct ForkForm {
+    /// Revision to fork. Defaults to the latest revision
+    revision: Option<i32>,
+    /// Requested lifetime of the fork. Capped by the configured maximum
+    expires: Option<Expiry>,
+    /// Only logged in users may create private forks
+    #[serde(default)]
+    visibility: Visibility,
+}
+
+/// Copy a paste into a new paste.
+///
+/// The request body is optional, without one the latest revision is forked with default settings.
+/// Password-protected pastes require their password in the `X-Paste-Password` header.
+/// The fork itself is not protected.
+#[tracing::instrument(
+    name = "Paste Fork Request",
+    skip(req, user, form, pool, store, max_expiry, pepper)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn fork_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    form: Option<web::Json<ForkForm>>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    max_expiry: web::Data<MaxExpiry>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    if let Some(response) = check_scope(user.as_ref(), Scope::PasteWrite) {
+        return response;
+    }
+
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => return locked_paste_response(),
+        Ok(_) => {}
+        Err(e) => return paste_error_response(e),
+    }
+
+    let form = form.map(|form| form.0).unwrap_or_default();
+    let fork = NewFork {
+        owner: user.as_ref().map(|user| *user.id()),
+        revision: form.revision,
+        expires_at: max_expiry.cap(form.expires).expires_at(Utc::now()),
+        visibility: form.visibility,
+    };
+
+    match Paste::fork(&short_id, &fork, &pool, store.get_ref()).await {
+        Ok(created) => HttpResponse::Created().json(ApiResponse::with_data(
+            "paste forked",
+            CreatedPasteData::from(created),
+        )),
+        Err(e) => paste_error_response(e),
+    }
+}
+
+/// List the forks of a paste.
+#[tracing::instrument(name = "Paste Fork Listing Request", skip(user, pool, store))]
+pub async fn list_forks(
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+) -> HttpResponse {
+    if let Some(response) = check_scope(user.as_ref(), Scope::PasteRead) {
+        return response;
+    }
+
+    let viewer = user.as_ref().map(CurrentUser::id);
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) => paste,
+        Err(e) => return paste_error_response(e),
+    };
+
+    match Paste::list_forks(&paste.id, viewer, &pool).await {
+        Ok(forks) => HttpResponse::Ok().json(ApiResponse::with_data(
+            "paste forks",
+            forks
+                .into_iter()
+                .map(PasteSummary::from)
+                .collect::<Vec<_>>(),
+        )),
+        Err(e) => paste_error_response(e),
+    }
+}
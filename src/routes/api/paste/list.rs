@@ -0,0 +1,24 @@
+use crate::entity::Paste;
+use crate::routes::api::ApiResponse;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use super::{paste_error_response, PasteSummary};
+
+/// Number of pastes returned when listing public pastes.
+const LISTING_LIMIT: i64 = 50;
+
+/// List the most recent public pastes.
+#[tracing::instrument(name = "Paste Listing Request", skip(pool))]
+pub async fn list_pastes(pool: web::Data<PgPool>) -> HttpResponse {
+    match Paste::list_public(LISTING_LIMIT, &pool).await {
+        Ok(pastes) => HttpResponse::Ok().json(ApiResponse::with_data(
+            "public pastes",
+            pastes
+                .into_iter()
+                .map(PasteSummary::from)
+                .collect::<Vec<_>>(),
+        )),
+        Err(e) => paste_error_response(e),
+    }
+}
@@ -0,0 +1,357 @@
+use crate::entity::{
+    Attachment, CreatedPaste, Editor, ForkOrigin, NewFile, Paste, PasteError, PasteFile, PasteKind,
+    Scope, Visibility,
+};
+use crate::routes::api::{ApiErrorMessage, ApiResponse};
+use crate::routes::session::CurrentUser;
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+mod attachment;
+mod create;
+mod delete;
+mod fork;
+mod list;
+mod read;
+mod update;
+
+pub use attachment::{add_attachment, delete_attachment};
+pub use create::create_paste;
+pub use delete::delete_paste;
+pub use fork::{fork_paste, list_forks};
+pub use list::list_pastes;
+pub use read::get_paste;
+pub use update::update_paste;
+
+/// Header that carries the edit token of a paste on modifying requests.
+const EDIT_TOKEN_HEADER: &str = "X-Edit-Token";
+
+/// Representation of a [`Paste`] as it is returned by the api.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PasteData {
+    /// Short id that addresses the paste in urls
+    id: String,
+    /// Vanity slug, under which the paste is also reachable as `/{username}/{slug}`
+    slug: Option<String>,
+    owner: Option<Uuid>,
+    title: Option<String>,
+    files: Vec<FileData>,
+    /// Binary files of the paste, without their content
+    attachments: Vec<AttachmentData>,
+    /// Number of the latest revision
+    revision: i32,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    burn_after_reading: bool,
+    visibility: Visibility,
+    /// Paste and revision this paste was forked from
+    forked_from: Option<ForkOrigin>,
+    /// Viewers have to send the password of the paste
+    password_protected: bool,
+    /// The content of encrypted pastes is ciphertext that only the client can decrypt
+    kind: PasteKind,
+}
+
+impl From<Paste> for PasteData {
+    fn from(paste: Paste) -> Self {
+        Self {
+            password_protected: paste.is_password_protected(),
+            id: paste.short_id,
+            slug: paste.slug,
+            owner: paste.owner,
+            title: paste.title,
+            files: paste.files.into_iter().map(FileData::from).collect(),
+            attachments: Vec::new(),
+            revision: paste.revision,
+            created_at: paste.created_at,
+            expires_at: paste.expires_at,
+            burn_after_reading: paste.burn_after_reading,
+            visibility: paste.visibility,
+            forked_from: paste.forked_from,
+            kind: paste.kind,
+        }
+    }
+}
+
+/// Representation of a [`PasteFile`] as it is returned by the api.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FileData {
+    filename: Option<String>,
+    content: String,
+    language: Option<String>,
+    /// Confidence of the detected language. `None` if the language was set explicitly
+    language_confidence: Option<f32>,
+}
+
+impl From<PasteFile> for FileData {
+    fn from(file: PasteFile) -> Self {
+        Self {
+            filename: file.filename,
+            content: file.content,
+            language: file.language,
+            language_confidence: file.language_confidence,
+        }
+    }
+}
+
+impl PasteData {
+    /// Add the attachments of the paste to its representation.
+    fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments.into_iter().map(AttachmentData::from).collect();
+        self
+    }
+}
+
+/// Representation of an [`Attachment`] as it is returned by the api.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AttachmentData {
+    filename: String,
+    /// Media type detected from the content
+    mime_type: String,
+    /// Size in bytes
+    size: i64,
+    created_at: DateTime<Utc>,
+}
+
+impl From<Attachment> for AttachmentData {
+    fn from(attachment: Attachment) -> Self {
+        Self {
+            filename: attachment.filename,
+            mime_type: attachment.mime_type,
+            size: attachment.size,
+            created_at: attachment.created_at,
+        }
+    }
+}
+
+/// A single file of a paste, as it is sent to the api.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FileForm {
+    /// Required if the paste holds more than one file
+    filename: Option<String>,
+    content: String,
+    language: Option<String>,
+}
+
+impl<'a> From<&'a FileForm> for NewFile<'a> {
+    fn from(file: &'a FileForm) -> Self {
+        Self {
+            filename: file.filename.as_deref(),
+            content: &file.content,
+            language: file.language.as_deref(),
+        }
+    }
+}
+
+/// Id and edit token of a newly created paste.
+#[derive(Debug, Clone, serde::Serialize)]
+struct CreatedPasteData {
+    id: String,
+    edit_token: String,
+}
+
+impl From<CreatedPaste> for CreatedPasteData {
+    fn from(created: CreatedPaste) -> Self {
+        Self {
+            id: created.short_id,
+            edit_token: created.edit_token,
+        }
+    }
+}
+
+/// Representation of a [`Paste`] in listings, without its content.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PasteSummary {
+    id: String,
+    title: Option<String>,
+    /// Language of the first file
+    language: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<Paste> for PasteSummary {
+    fn from(paste: Paste) -> Self {
+        Self {
+            language: paste.language().map(str::to_owned),
+            id: paste.short_id,
+            title: paste.title,
+            created_at: paste.created_at,
+            expires_at: paste.expires_at,
+        }
+    }
+}
+
+/// Retrieve the edit token sent along with a request, if there is one.
+fn edit_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(EDIT_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Determine how a modifying request proves that it may change a paste.
+///
+/// Requests send the edit token of the paste or, without one, come from the owner of the paste, whose api token
+/// has to grant `scope`. Whether the paste accepts the proof is checked by the change itself.
+/// Returns the response to send if the request has no proof to offer.
+#[allow(clippy::result_large_err)]
+fn editor<'a>(
+    req: &'a HttpRequest,
+    user: Option<&'a CurrentUser>,
+    scope: Scope,
+) -> Result<Editor<'a>, HttpResponse> {
+    match (edit_token(req), user) {
+        (Some(token), _) => Ok(Editor::EditToken(token)),
+        (None, Some(user)) => match check_scope(Some(user), scope) {
+            Some(response) => Err(response),
+            None => Ok(Editor::Owner(user.id())),
+        },
+        (None, None) => Err(missing_edit_token_response()),
+    }
+}
+
+/// Check that a request authenticated by an api token was granted `scope`.
+///
+/// Returns the response to send if the token lacks the scope. Sessions and anonymous requests always pass.
+fn check_scope(user: Option<&CurrentUser>, scope: Scope) -> Option<HttpResponse> {
+    if user.is_none_or(|user| user.has_scope(scope)) {
+        return None;
+    }
+
+    Some(HttpResponse::Forbidden().json(ApiResponse::with_errors(
+        false,
+        "api token lacks the required scope",
+        vec![ApiErrorMessage::missing_scope()],
+    )))
+}
+
+/// Translate an error returned by [`Paste`] operations into an api response.
+fn paste_error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref() {
+        Some(PasteError::PasteNotFound) => HttpResponse::NotFound().json(ApiResponse::with_errors(
+            false,
+            "paste not found",
+            vec![ApiErrorMessage::paste_not_found()],
+        )),
+        Some(PasteError::RevisionNotFound) => {
+            HttpResponse::NotFound().json(ApiResponse::with_errors(
+                false,
+                "paste revision not found",
+                vec![ApiErrorMessage::revision_not_found()],
+            ))
+        }
+        Some(PasteError::PrivateWithoutOwner) => {
+            HttpResponse::BadRequest().json(ApiResponse::with_errors(
+                false,
+                "invalid paste",
+                vec![ApiErrorMessage::private_requires_owner()],
+            ))
+        }
+        Some(PasteError::InvalidFiles) => invalid_files_response(),
+        Some(PasteError::PasteTooLarge) => paste_too_large_response(),
+        Some(PasteError::AttachmentNotFound) => {
+            HttpResponse::NotFound().json(ApiResponse::with_errors(
+                false,
+                "attachment not found",
+                vec![ApiErrorMessage::attachment_not_found()],
+            ))
+        }
+        Some(PasteError::InvalidAttachment) => {
+            HttpResponse::BadRequest().json(ApiResponse::with_errors(
+                false,
+                "invalid attachment",
+                vec![ApiErrorMessage::invalid_attachment()],
+            ))
+        }
+        Some(PasteError::InvalidEncryptedPaste) => {
+            HttpResponse::BadRequest().json(ApiResponse::with_errors(
+                false,
+                "invalid encrypted paste",
+                vec![ApiErrorMessage::invalid_encrypted_paste()],
+            ))
+        }
+        Some(PasteError::InvalidSlug) => HttpResponse::BadRequest().json(ApiResponse::with_errors(
+            false,
+            "invalid slug",
+            vec![ApiErrorMessage::invalid_slug()],
+        )),
+        Some(PasteError::SlugTaken) => HttpResponse::Conflict().json(ApiResponse::with_errors(
+            false,
+            "slug is already taken",
+            vec![ApiErrorMessage::slug_taken()],
+        )),
+        Some(PasteError::InvalidEditToken) => {
+            HttpResponse::Forbidden().json(ApiResponse::with_errors(
+                false,
+                "invalid edit token",
+                vec![ApiErrorMessage::invalid_edit_token()],
+            ))
+        }
+        None => {
+            tracing::error!("error while processing paste request: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(ApiResponse::new(false, "error while processing request"))
+        }
+    }
+}
+
+/// Response for requests to a password-protected paste that come without its correct password.
+fn locked_paste_response() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ApiResponse::with_errors(
+        false,
+        "missing or invalid paste password",
+        vec![ApiErrorMessage::invalid_paste_password()],
+    ))
+}
+
+/// Response for modifying requests that come without an edit token.
+fn missing_edit_token_response() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ApiResponse::with_errors(
+        false,
+        "missing edit token",
+        vec![ApiErrorMessage::invalid_edit_token()],
+    ))
+}
+
+/// Response for requests that try to store a paste that exceeds the configured maximum size.
+fn paste_too_large_response() -> HttpResponse {
+    HttpResponse::PayloadTooLarge().json(ApiResponse::with_errors(
+        false,
+        "invalid paste",
+        vec![ApiErrorMessage::paste_too_large()],
+    ))
+}
+
+/// Check the size and content of the files of a paste.
+///
+/// Returns the response to send if a file is empty or all files together exceed `max_size`.
+fn check_files(files: &[NewFile], max_size: usize) -> Option<HttpResponse> {
+    if files.iter().any(|file| file.content.trim().is_empty()) {
+        return Some(empty_content_response());
+    }
+
+    if files.iter().map(|file| file.content.len()).sum::<usize>() > max_size {
+        return Some(paste_too_large_response());
+    }
+
+    None
+}
+
+/// Response for requests whose files can not be stored together as one paste.
+fn invalid_files_response() -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiResponse::with_errors(
+        false,
+        "invalid paste",
+        vec![ApiErrorMessage::invalid_files()],
+    ))
+}
+
+/// Response for requests that try to store a paste without content.
+fn empty_content_response() -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiResponse::with_errors(
+        false,
+        "invalid paste",
+        vec![ApiErrorMessage::empty_content()],
+    ))
+}
@@ -0,0 +1,60 @@
+use crate::entity::{Attachment, Paste, Scope};
+use crate::routes::api::ApiResponse;
+use crate::routes::paste::is_unlocked;
+use crate::routes::session::{CurrentUser, OptionalUser};
+use crate::setup::Pepper;
+use crate::storage::BlobStore;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+use super::{check_scope, locked_paste_response, paste_error_response, PasteData};
+
+/// Retrieve a paste.
+///
+/// Burn-after-reading pastes are deleted by this request,
+/// only the first client to retrieve them receives the content.
+/// Attachments are listed without their content, which is served at `/{id}/attachments/{filename}`.
+/// Password-protected pastes require their password in the `X-Paste-Password` header.
+#[tracing::instrument(name = "Paste Retrieval Request", skip(req, user, pool, store, pepper))]
+pub async fn get_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    if let Some(response) = check_scope(user.as_ref(), Scope::PasteRead) {
+        return response;
+    }
+
+    let viewer = user.as_ref().map(CurrentUser::id);
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => {
+            return locked_paste_response();
+        }
+        Ok(paste) if paste.burn_after_reading => {
+            Paste::burn(&paste.id, viewer, &pool, store.get_ref()).await
+        }
+        result => result,
+    };
+
+    let paste = match paste {
+        Ok(paste) => paste,
+        Err(e) => return paste_error_response(e),
+    };
+
+    // burn-after-reading pastes can not have attachments, and are gone by now anyway
+    let attachments = match paste.burn_after_reading {
+        true => Vec::new(),
+        false => match Attachment::list(&paste.id, &pool).await {
+            Ok(attachments) => attachments,
+            Err(e) => return paste_error_response(e),
+        },
+    };
+
+    HttpResponse::Ok().json(ApiResponse::with_data(
+        "paste found",
+        PasteData::from(paste).with_attachments(attachments),
+    ))
+}
@@ -0,0 +1,92 @@
+use crate::entity::{NewFile, Paste, PasteUpdate, Scope};
+use crate::routes::api::ApiResponse;
+use crate::routes::session::OptionalUser;
+use crate::setup::MaxPasteSize;
+use crate::storage::BlobStore;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+use super::{
+    check_files, editor, empty_content_response, invalid_files_response, paste_error_response,
+    paste_too_large_response, FileForm,
+};
+
+#[derive(serde::Deserialize)]
+pub struct PasteUpdateForm {
+    title: Option<String>,
+    /// Replaces the content of the first file. Can not be combined with `files`
+    content: Option<String>,
+    /// Replaces the language of the first file
+    language: Option<String>,
+    /// Replaces all files of the paste
+    files: Option<Vec<FileForm>>,
+}
+
+#[tracing::instrument(
+    name = "Paste Update Request",
+    skip(req, user, pool, store, form, max_size)
+)]
+pub async fn update_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    form: web::Json<PasteUpdateForm>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    max_size: web::Data<MaxPasteSize>,
+) -> HttpResponse {
+    let editor = match editor(&req, user.as_ref(), Scope::PasteWrite) {
+        Ok(editor) => editor,
+        Err(response) => return response,
+    };
+
+    let PasteUpdateForm {
+        title,
+        content,
+        language,
+        files,
+    } = form.0;
+
+    if content.is_some() && files.is_some() {
+        return invalid_files_response();
+    }
+
+    let files: Option<Vec<NewFile>> = files
+        .as_ref()
+        .map(|files| files.iter().map(NewFile::from).collect());
+    if let Some(response) = files
+        .as_deref()
+        .and_then(|files| check_files(files, max_size.0))
+    {
+        return response;
+    }
+
+    if content.as_deref().is_some_and(|c| c.trim().is_empty()) {
+        return empty_content_response();
+    }
+
+    if content.as_deref().is_some_and(|c| c.len() > max_size.0) {
+        return paste_too_large_response();
+    }
+
+    let update = PasteUpdate {
+        title: title.as_deref(),
+        files,
+        content: content.as_deref(),
+        language: language.as_deref(),
+    };
+
+    match Paste::update(
+        &short_id,
+        editor,
+        &update,
+        max_size.0,
+        &pool,
+        store.get_ref(),
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::Ok().json(ApiResponse::new(true, "paste updated")),
+        Err(e) => paste_error_response(e),
+    }
+}
@@ -0,0 +1,61 @@
+use crate::entity::{User, UserError};
+use crate::mail;
+use crate::routes::api::ApiResponse;
+use crate::setup::{AppBaseUrl, SendGridApiKey};
+use actix_web::{web, HttpResponse};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct ResendData {
+    mail: String,
+}
+
+/// Send a new activation mail to an account that has not been activated yet.
+///
+/// The response is the same whether or not there is such an account. The mail is sent in the background,
+/// so the response time does not tell either, and requests within the cooldown are dropped silently.
+/// If the mail can not be sent, the cooldown is lifted again, since the previous link stopped working.
+#[tracing::instrument(
+    name = "Activation Resend Request",
+    skip(form, pool, base_url, sendgrid_key)
+)]
+pub async fn resend_activation(
+    form: web::Json<ResendData>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<AppBaseUrl>,
+    sendgrid_key: web::Data<SendGridApiKey>,
+) -> HttpResponse {
+    let accepted = HttpResponse::Accepted().json(ApiResponse::new(
+        true,
+        "if the address belongs to an account that is not activated, a new activation mail is on its way",
+    ));
+
+    let token = match User::renew_confirmation(&form.mail, &pool).await {
+        Ok(Some(token)) => token,
+        Ok(None) => return accepted,
+        Err(e) => match e.downcast_ref() {
+            Some(UserError::ActivationCooldown) => return accepted,
+            _ => {
+                tracing::error!("error while renewing confirmation: {:?}", e);
+                return HttpResponse::InternalServerError()
+                    .json(ApiResponse::new(false, "error while processing request"));
+            }
+        },
+    };
+
+    let mail = form.0.mail;
+    let base_url = base_url.0.clone();
+    let api_key = sendgrid_key.0.expose_secret().clone();
+    let pool = pool.into_inner();
+    actix_web::rt::spawn(async move {
+        let Err(e) = mail::send_registration_mail(&token, &mail, &base_url, &api_key).await else {
+            return;
+        };
+        tracing::error!("error sending activation mail: {:?}", e);
+        if let Err(e) = User::release_activation_cooldown(&token, &pool).await {
+            tracing::error!("error while lifting activation cooldown: {:?}", e);
+        }
+    });
+    accepted
+}
@@ -0,0 +1,88 @@
+use crate::auth::AuthError;
+use crate::entity::{User, UserError};
+use crate::routes::api::{ApiErrorMessage, ApiResponse};
+use crate::routes::session::{removal_cookie, session_cookie, SESSION_COOKIE};
+use crate::setup::Pepper;
+use actix_web::{web, HttpRequest, HttpResponse};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct LoginData {
+    username: String,
+    password: String,
+}
+
+/// Name of the logged in user.
+#[derive(Debug, Clone, serde::Serialize)]
+struct LoginResult<'a> {
+    username: &'a str,
+}
+
+/// Log a user in and start a session, whose token is handed out in the session cookie.
+#[tracing::instrument(name = "Login Request", skip(form, pool, pepper), fields(username = %form.username))]
+pub async fn login_user(
+    form: web::Json<LoginData>,
+    pool: web::Data<PgPool>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let pepper = pepper.0.expose_secret().as_bytes().to_vec();
+    let user = match User::login(&form.username, &form.password, &pool, pepper).await {
+        Ok(user) => user,
+        Err(e) => {
+            if let Some(UserError::NotActivated) = e.downcast_ref() {
+                return HttpResponse::Forbidden().json(ApiResponse::with_errors(
+                    false,
+                    "account not activated",
+                    vec![ApiErrorMessage::account_not_activated()],
+                ));
+            }
+            match e.downcast_ref() {
+                Some(AuthError::InvalidCredentials(_)) => {
+                    return HttpResponse::Unauthorized().json(ApiResponse::with_errors(
+                        false,
+                        "invalid credentials",
+                        vec![ApiErrorMessage::invalid_credentials()],
+                    ))
+                }
+                _ => {
+                    tracing::error!("error while logging in: {:?}", e);
+                    return HttpResponse::InternalServerError()
+                        .json(ApiResponse::new(false, "error while processing request"));
+                }
+            }
+        }
+    };
+
+    match user.start_session(&pool).await {
+        Ok(token) => HttpResponse::Ok()
+            .cookie(session_cookie(token))
+            .json(ApiResponse::with_data(
+                "login successful",
+                LoginResult {
+                    username: user.username(),
+                },
+            )),
+        Err(e) => {
+            tracing::error!("error while starting session: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(ApiResponse::new(false, "error while processing request"))
+        }
+    }
+}
+
+/// End the session of the request, if there is one, and remove the session cookie.
+#[tracing::instrument(name = "Logout Request", skip(req, pool))]
+pub async fn logout_user(req: HttpRequest, pool: web::Data<PgPool>) -> HttpResponse {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        if let Err(e) = User::end_session(cookie.value(), &pool).await {
+            tracing::error!("error while ending session: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(ApiResponse::new(false, "error while processing request"));
+        }
+    }
+
+    HttpResponse::Ok()
+        .cookie(removal_cookie())
+        .json(ApiResponse::new(true, "logout successful"))
+}
@@ -1,3 +1,11 @@
+mod activation;
+mod login;
+mod password;
 mod register;
+mod tokens;
 
+pub use activation::resend_activation;
+pub use login::{login_user, logout_user};
+pub use password::{forgot_password, reset_password};
 pub use register::register_user;
+pub use tokens::{create_token, delete_token, list_tokens};
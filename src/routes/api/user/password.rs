@@ -0,0 +1,111 @@
+use crate::entity::{User, UserError};
+use crate::mail;
+use crate::routes::api::{ApiErrorMessage, ApiResponse};
+use crate::routes::session::removal_cookie;
+use crate::setup::{AppBaseUrl, Pepper, SendGridApiKey};
+use actix_web::{web, HttpResponse};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct ForgotData {
+    mail: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetData {
+    /// Token from the link in the reset mail
+    token: String,
+    password: String,
+}
+
+/// Mail a password reset link to the account with the given address.
+///
+/// The response is the same whether or not the address belongs to an account. The mail is sent in the background,
+/// so the response time does not tell either, and requests within the cooldown are dropped silently.
+/// If the mail can not be sent, the cooldown is lifted again, since the previous link stopped working.
+#[tracing::instrument(
+    name = "Password Forgot Request",
+    skip(form, pool, base_url, sendgrid_key)
+)]
+pub async fn forgot_password(
+    form: web::Json<ForgotData>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<AppBaseUrl>,
+    sendgrid_key: web::Data<SendGridApiKey>,
+) -> HttpResponse {
+    let accepted = HttpResponse::Accepted().json(ApiResponse::new(
+        true,
+        "if the address belongs to an account, a password reset link is on its way",
+    ));
+
+    let token = match User::request_password_reset(&form.mail, &pool).await {
+        Ok(Some(token)) => token,
+        Ok(None) => return accepted,
+        Err(e) => match e.downcast_ref() {
+            Some(UserError::ResetCooldown) => return accepted,
+            _ => {
+                tracing::error!("error while requesting password reset: {:?}", e);
+                return HttpResponse::InternalServerError()
+                    .json(ApiResponse::new(false, "error while processing request"));
+            }
+        },
+    };
+
+    let mail = form.0.mail;
+    let base_url = base_url.0.clone();
+    let api_key = sendgrid_key.0.expose_secret().clone();
+    let pool = pool.into_inner();
+    actix_web::rt::spawn(async move {
+        let Err(e) = mail::send_password_reset_mail(&token, &mail, &base_url, &api_key).await
+        else {
+            return;
+        };
+        tracing::error!("error sending password reset mail: {:?}", e);
+        if let Err(e) = User::release_reset_cooldown(&token, &pool).await {
+            tracing::error!("error while lifting password reset cooldown: {:?}", e);
+        }
+    });
+    accepted
+}
+
+/// Set a new password with the token from a reset mail.
+///
+/// Ends all sessions of the user, including the one of this client, if any, and revokes their api tokens.
+#[tracing::instrument(name = "Password Reset Request", skip(form, pool, pepper))]
+pub async fn reset_password(
+    form: web::Json<ResetData>,
+    pool: web::Data<PgPool>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let ResetData { token, password } = form.0;
+
+    if password.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::with_errors(
+            false,
+            "invalid password",
+            vec![ApiErrorMessage::empty_password()],
+        ));
+    }
+
+    let pepper = pepper.0.expose_secret().as_bytes().to_vec();
+    match User::reset_password(&token, password, &pool, pepper).await {
+        Ok(()) => HttpResponse::Ok()
+            .cookie(removal_cookie())
+            .json(ApiResponse::new(true, "password changed")),
+        Err(e) => match e.downcast_ref() {
+            Some(UserError::InvalidResetToken) => {
+                HttpResponse::BadRequest().json(ApiResponse::with_errors(
+                    false,
+                    "invalid reset token",
+                    vec![ApiErrorMessage::invalid_reset_token()],
+                ))
+            }
+            _ => {
+                tracing::error!("error while resetting password: {:?}", e);
+                HttpResponse::InternalServerError()
+                    .json(ApiResponse::new(false, "error while processing request"))
+            }
+        },
+    }
+}
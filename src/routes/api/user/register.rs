@@ -29,7 +29,7 @@ pub async fn register_user(
         password,
     } = form_data;
 
-    let new_user_id = match User::create(
+    let activation_token = match User::create(
         &mail,
         &username,
         password,
@@ -38,7 +38,7 @@ pub async fn register_user(
     )
     .await
     {
-        Ok(id) => id,
+        Ok(token) => token,
         Err(e) => match e.downcast_ref() {
             Some(UserError::UserAlreadyExists) => {
                 return HttpResponse::Conflict().json(ApiResponse::with_errors(
@@ -47,7 +47,14 @@ pub async fn register_user(
                     vec![ApiErrorMessage::user_already_exists()],
                 ))
             }
-            None => {
+            Some(UserError::ReservedUsername) => {
+                return HttpResponse::BadRequest().json(ApiResponse::with_errors(
+                    false,
+                    "username is reserved",
+                    vec![ApiErrorMessage::reserved_username()],
+                ))
+            }
+            _ => {
                 return HttpResponse::InternalServerError()
                     .json(ApiResponse::new(false, "error while processing request"))
             }
@@ -55,7 +62,7 @@ pub async fn register_user(
     };
 
     match mail::send_registration_mail(
-        &new_user_id,
+        &activation_token,
         &mail,
         &base_url.0,
         sendgrid_key.0.expose_secret(),
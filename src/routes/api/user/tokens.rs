@@ -0,0 +1,149 @@
+use crate::entity::{ApiToken, ApiTokenError, NewApiToken, Scope};
+use crate::routes::api::{ApiErrorMessage, ApiResponse};
+use crate::routes::session::CurrentUser;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct TokenForm {
+    /// Tells the tokens of a user apart, e.g. the CI job that uses it
+    name: String,
+    scopes: Vec<Scope>,
+    /// The token does not expire if this is left out
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Representation of an [`ApiToken`] as it is returned by the api, without the token itself.
+#[derive(Debug, Clone, serde::Serialize)]
+struct TokenData {
+    id: Uuid,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for TokenData {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+        }
+    }
+}
+
+/// A newly created api token. `token` is only shown once.
+#[derive(Debug, Clone, serde::Serialize)]
+struct CreatedTokenData {
+    token: String,
+    #[serde(flatten)]
+    details: TokenData,
+}
+
+/// Create an api token that authenticates requests as the logged in user via `Authorization: Bearer`.
+#[tracing::instrument(name = "Api Token Creation Request", skip(user, form, pool))]
+pub async fn create_token(
+    user: CurrentUser,
+    form: web::Json<TokenForm>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if !user.is_session() {
+        return session_required_response();
+    }
+
+    let token = NewApiToken {
+        name: &form.name,
+        scopes: &form.scopes,
+        expires_at: form.expires_at,
+    };
+
+    match ApiToken::create(user.id(), &token, &pool).await {
+        Ok((token, secret)) => HttpResponse::Created().json(ApiResponse::with_data(
+            "api token created",
+            CreatedTokenData {
+                token: secret,
+                details: TokenData::from(token),
+            },
+        )),
+        Err(e) => token_error_response(e),
+    }
+}
+
+/// List the api tokens of the logged in user.
+#[tracing::instrument(name = "Api Token Listing Request", skip(user, pool))]
+pub async fn list_tokens(user: CurrentUser, pool: web::Data<PgPool>) -> HttpResponse {
+    if !user.is_session() {
+        return session_required_response();
+    }
+
+    match ApiToken::list(user.id(), &pool).await {
+        Ok(tokens) => HttpResponse::Ok().json(ApiResponse::with_data(
+            "api tokens",
+            tokens.into_iter().map(TokenData::from).collect::<Vec<_>>(),
+        )),
+        Err(e) => token_error_response(e),
+    }
+}
+
+/// Revoke an api token of the logged in user.
+#[tracing::instrument(name = "Api Token Deletion Request", skip(user, pool))]
+pub async fn delete_token(
+    user: CurrentUser,
+    id: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if !user.is_session() {
+        return session_required_response();
+    }
+
+    // a malformed id can not belong to any token, so it gets the same response as an unknown one
+    let Ok(id) = Uuid::parse_str(&id) else {
+        return token_error_response(ApiTokenError::ApiTokenNotFound.into());
+    };
+
+    match ApiToken::delete(user.id(), &id, &pool).await {
+        Ok(()) => HttpResponse::Ok().json(ApiResponse::new(true, "api token deleted")),
+        Err(e) => token_error_response(e),
+    }
+}
+
+/// Response for requests that try to manage api tokens with an api token.
+///
+/// Otherwise a leaked token could be used to mint new tokens that outlive its revocation.
+fn session_required_response() -> HttpResponse {
+    HttpResponse::Forbidden().json(ApiResponse::with_errors(
+        false,
+        "login required",
+        vec![ApiErrorMessage::session_required()],
+    ))
+}
+
+/// Translate an error returned by [`ApiToken`] operations into an api response.
+fn token_error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref() {
+        Some(ApiTokenError::InvalidApiToken) => {
+            HttpResponse::BadRequest().json(ApiResponse::with_errors(
+                false,
+                "invalid api token",
+                vec![ApiErrorMessage::invalid_api_token()],
+            ))
+        }
+        Some(ApiTokenError::ApiTokenNotFound) => {
+            HttpResponse::NotFound().json(ApiResponse::with_errors(
+                false,
+                "api token not found",
+                vec![ApiErrorMessage::api_token_not_found()],
+            ))
+        }
+        None => {
+            tracing::error!("error while processing api token request: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(ApiResponse::new(false, "error while processing request"))
+        }
+    }
+}
@@ -1,12 +1,45 @@
+use crate::entity::Paste;
+use actix_web::web;
 use actix_web_lab::respond::Html;
 use askama::Template;
+use sqlx::PgPool;
+
+/// Number of public pastes shown on the index page.
+const RECENT_PASTES: i64 = 10;
+
+/// Public paste as it is listed on the index page.
+struct RecentPaste {
+    id: String,
+    title: String,
+    language: String,
+    created_at: String,
+}
 
 #[derive(Template)]
 #[template(path = "index.html")]
-struct IndexPage;
+struct IndexPage {
+    recent: Vec<RecentPaste>,
+}
+
+pub async fn index_page(pool: web::Data<PgPool>) -> Html {
+    let recent = match Paste::list_public(RECENT_PASTES, &pool).await {
+        Ok(pastes) => pastes
+            .into_iter()
+            .map(|paste| RecentPaste {
+                language: paste.language().unwrap_or("plain text").to_owned(),
+                id: paste.short_id,
+                title: paste.title.unwrap_or_else(|| "Untitled paste".into()),
+                created_at: paste.created_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+            })
+            .collect(),
+        Err(e) => {
+            // the index page should still be usable without the listing
+            tracing::error!("error while listing public pastes: {:?}", e);
+            Vec::new()
+        }
+    };
 
-pub async fn index_page() -> Html {
     // unwrapping is safe here
-    let html = IndexPage.render().unwrap();
+    let html = IndexPage { recent }.render().unwrap();
     Html(html)
 }
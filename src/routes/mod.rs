@@ -4,6 +4,8 @@ use askama::Template;
 pub mod api;
 pub mod healthcheck;
 pub mod index;
+pub mod paste;
+pub mod session;
 pub mod user;
 
 #[derive(askama::Template)]
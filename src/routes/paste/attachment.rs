@@ -0,0 +1,61 @@
+use super::raw::{disposition_filename, raw_error_response};
+use super::unlock::{is_unlocked, locked_response};
+use crate::entity::{Attachment, Paste, PasteError, Scope};
+use crate::routes::session::OptionalUser;
+use crate::setup::Pepper;
+use crate::sniff::is_inline_image;
+use crate::storage::BlobStore;
+use actix_web::http::header::{
+    ContentDisposition, ContentType, DispositionType, X_CONTENT_TYPE_OPTIONS,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+/// Serve an attachment of a paste.
+///
+/// The `Content-Type` is the media type that was detected from the content when it was stored, and browsers are told
+/// not to second-guess it. Raster images are shown inline, everything else is offered as download.
+#[tracing::instrument(name = "Attachment Request", skip(req, user, pool, store, pepper))]
+pub async fn view_attachment(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let (short_id, filename) = path.into_inner();
+
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => return locked_response(&paste),
+        Ok(paste) => paste,
+        Err(e) => return raw_error_response(e),
+    };
+
+    let (attachment, data) =
+        match Attachment::fetch_data(&paste.id, &filename, &pool, store.get_ref()).await {
+            Ok(attachment) => attachment,
+            Err(e) if matches!(e.downcast_ref(), Some(PasteError::AttachmentNotFound)) => {
+                return HttpResponse::NotFound()
+                    .content_type(ContentType::plaintext())
+                    .body("attachment not found\n");
+            }
+            Err(e) => return raw_error_response(e),
+        };
+
+    let disposition = ContentDisposition {
+        disposition: if is_inline_image(&attachment.mime_type) {
+            DispositionType::Inline
+        } else {
+            DispositionType::Attachment
+        },
+        parameters: disposition_filename(&attachment.filename),
+    };
+
+    HttpResponse::Ok()
+        .content_type(attachment.mime_type)
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header(disposition)
+        .body(data)
+}
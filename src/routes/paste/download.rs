@@ -0,0 +1,99 @@
+use super::raw::{disposition_filename, raw_error_response};
+use super::unlock::{is_unlocked, locked_response};
+use crate::archive::ArchiveFormat;
+use crate::entity::{Paste, Scope};
+use crate::routes::session::OptionalUser;
+use crate::setup::Pepper;
+use crate::storage::BlobStore;
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionType};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+/// Offer all files of a paste as a single `.zip` or `.tar.gz` archive.
+///
+/// The files are placed in a directory named after the short id of the paste.
+/// Burn-after-reading pastes are not served, they have to be downloaded via [`burn_download_paste`].
+#[tracing::instrument(name = "Paste Download Request", skip(req, user, pool, store, pepper))]
+pub async fn download_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<(String, ArchiveFormat)>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let (short_id, format) = path.into_inner();
+
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => locked_response(&paste),
+        Ok(paste) if paste.burn_after_reading => HttpResponse::Forbidden()
+            .content_type(ContentType::plaintext())
+            .body(
+                "this paste is deleted after reading. \
+                send a POST request to this url to download it, e.g. `curl -X POST`\n",
+            ),
+        Ok(paste) => archive_response(paste, format).await,
+        Err(e) => raw_error_response(e),
+    }
+}
+
+/// Offer all files of a burn-after-reading paste as a single archive and delete it.
+///
+/// Pastes that are not burn-after-reading are served like in [`download_paste`].
+#[tracing::instrument(
+    name = "Paste Download Burn Request",
+    skip(req, user, pool, store, pepper)
+)]
+pub async fn burn_download_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<(String, ArchiveFormat)>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let (short_id, format) = path.into_inner();
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => {
+            return locked_response(&paste);
+        }
+        Ok(paste) if paste.burn_after_reading => {
+            Paste::burn(&paste.id, viewer, &pool, store.get_ref()).await
+        }
+        result => result,
+    };
+
+    match paste {
+        Ok(paste) => archive_response(paste, format).await,
+        Err(e) => raw_error_response(e),
+    }
+}
+
+/// Build the response carrying the archive of `paste`. Compression happens on the blocking thread pool.
+async fn archive_response(paste: Paste, format: ArchiveFormat) -> HttpResponse {
+    let filename = format!("{}.{}", paste.short_id, format.as_str());
+    let archive = web::block(move || {
+        let files: Vec<_> = paste
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (file.download_name(i), file.content.as_bytes()))
+            .collect();
+        format.write(&paste.short_id, &files, paste.created_at)
+    })
+    .await;
+
+    match archive {
+        Ok(Ok(archive)) => HttpResponse::Ok()
+            .content_type(format.mime_type())
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: disposition_filename(&filename),
+            })
+            .body(archive),
+        Ok(Err(e)) => raw_error_response(e.into()),
+        Err(e) => raw_error_response(e.into()),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::entity::Paste;
+use actix_web_lab::respond::Html;
+use askama::Template;
+
+/// Page of an encrypted paste. The ciphertext is decrypted by `static/js/encrypted.js`,
+/// with the key from the fragment of the url.
+#[derive(Template)]
+#[template(path = "encrypted.html")]
+struct EncryptedPage<'a> {
+    id: &'a str,
+    ciphertext: &'a str,
+    created_at: String,
+    expires_at: Option<String>,
+    /// The paste was deleted while rendering this page
+    burned: bool,
+}
+
+#[derive(Template)]
+#[template(path = "encrypt.html")]
+struct NewEncryptedPage;
+
+/// Show the form that encrypts a paste in the browser before creating it via the api.
+pub async fn new_encrypted_paste() -> Html {
+    let html = NewEncryptedPage.render().unwrap();
+    Html(html)
+}
+
+/// Render the page of an encrypted paste, which is decrypted in the browser.
+pub(super) fn encrypted_page(paste: &Paste, burned: bool) -> Html {
+    let page = EncryptedPage {
+        id: &paste.short_id,
+        ciphertext: paste
+            .files
+            .first()
+            .map(|file| file.content.as_str())
+            .unwrap_or_default(),
+        created_at: paste.created_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+        expires_at: paste
+            .expires_at
+            .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string()),
+        burned,
+    };
+    Html(page.render().unwrap())
+}
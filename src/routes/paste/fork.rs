@@ -0,0 +1,133 @@
+use crate::entity::{NewFork, Paste, Scope, Visibility};
+use crate::routes::session::OptionalUser;
+use crate::setup::{MaxExpiry, Pepper};
+use crate::storage::BlobStore;
+use actix_web::{
+    web::{self, Redirect},
+    Either, HttpRequest,
+};
+use actix_web_lab::respond::Html;
+use askama::Template;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use super::unlock::is_unlocked;
+use super::view::not_found;
+
+/// Fork as it is shown in the list of forks.
+struct ForkEntry {
+    id: String,
+    title: String,
+    revision: i32,
+    created_at: String,
+}
+
+#[derive(Template)]
+#[template(path = "forks.html")]
+struct ForksPage<'a> {
+    id: &'a str,
+    title: &'a str,
+    forks: Vec<ForkEntry>,
+}
+
+/// Page shown after an anonymous user forked a paste, since only the edit token allows changing the fork.
+#[derive(Template)]
+#[template(path = "forked.html")]
+struct ForkedPage<'a> {
+    id: &'a str,
+    edit_token: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ForkRequest {
+    /// Revision to fork. Defaults to the latest revision
+    revision: Option<i32>,
+}
+
+/// Fork a paste from the fork buttons of the paste and revision pages and show the new paste.
+///
+/// Anonymous users are shown the edit token of the fork instead, since they can not change it otherwise.
+///
+/// Password-protected pastes that have not been unlocked are redirected to their page, which asks for the password.
+#[tracing::instrument(
+    name = "Paste Fork Page Request",
+    skip(req, user, form, pool, store, max_expiry, pepper)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_fork(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    form: web::Form<ForkRequest>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    max_expiry: web::Data<MaxExpiry>,
+    pepper: web::Data<Pepper>,
+) -> Either<Html, Redirect> {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => {
+            return Either::Right(Redirect::to(format!("/{}", paste.short_id)).see_other());
+        }
+        Ok(_) => {}
+        Err(e) => return not_found(e),
+    }
+
+    let fork = NewFork {
+        owner: user
+            .as_ref()
+            .and_then(|user| user.id_for(Scope::PasteWrite))
+            .copied(),
+        revision: form.revision,
+        expires_at: max_expiry.cap(None).expires_at(Utc::now()),
+        visibility: Visibility::Unlisted,
+    };
+
+    match Paste::fork(&short_id, &fork, &pool, store.get_ref()).await {
+        Ok(created) if fork.owner.is_none() => Either::Left(Html(
+            ForkedPage {
+                id: &created.short_id,
+                edit_token: &created.edit_token,
+            }
+            .render()
+            .unwrap(),
+        )),
+        Ok(created) => Either::Right(Redirect::to(format!("/{}", created.short_id)).see_other()),
+        Err(e) => not_found(e),
+    }
+}
+
+#[tracing::instrument(name = "Paste Forks Page Request", skip(user, pool, store))]
+pub async fn forks_page(
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+) -> Either<Html, Redirect> {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) => paste,
+        Err(e) => return not_found(e),
+    };
+    let forks = match Paste::list_forks(&paste.id, viewer, &pool).await {
+        Ok(forks) => forks,
+        Err(e) => return not_found(e),
+    };
+
+    let page = ForksPage {
+        id: &paste.short_id,
+        title: paste.title.as_deref().unwrap_or("Untitled paste"),
+        forks: forks
+            .into_iter()
+            .filter_map(|fork| {
+                Some(ForkEntry {
+                    id: fork.short_id,
+                    title: fork.title.unwrap_or_else(|| "Untitled paste".to_owned()),
+                    revision: fork.forked_from?.revision,
+                    created_at: fork.created_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+                })
+            })
+            .collect(),
+    };
+    Either::Left(Html(page.render().unwrap()))
+}
@@ -0,0 +1,23 @@
+mod attachment;
+mod download;
+mod encrypted;
+mod fork;
+mod raw;
+mod revisions;
+mod unlock;
+mod upload;
+mod vanity;
+mod view;
+
+pub(crate) use unlock::{is_unlocked, PASSWORD_HEADER};
+
+pub use attachment::view_attachment;
+pub use download::{burn_download_paste, download_paste};
+pub use encrypted::new_encrypted_paste;
+pub use fork::{create_fork, forks_page};
+pub use raw::{burn_raw_paste, raw_paste, raw_paste_file};
+pub use revisions::{paste_diff, paste_revisions, select_diff};
+pub use unlock::unlock_paste;
+pub use upload::upload_paste;
+pub use vanity::vanity_paste;
+pub use view::{burn_paste, view_paste};
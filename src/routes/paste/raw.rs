@@ -0,0 +1,349 @@
+use super::unlock::{is_unlocked, locked_response};
+use crate::entity::{Paste, PasteError, PasteFile, Scope};
+use crate::routes::session::OptionalUser;
+use crate::setup::Pepper;
+use crate::storage::BlobStore;
+use actix_web::http::header::{
+    Charset, ContentDisposition, ContentType, DispositionParam, DispositionType, ExtendedValue,
+    ACCEPT_ENCODING, CONTENT_ENCODING, VARY,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct RawQuery {
+    /// Ask the client to save the paste instead of displaying it
+    #[serde(default)]
+    download: bool,
+}
+
+/// Serve the content of the first file of a paste as plain text, e.g. for `curl` and `wget`.
+///
+/// The filename announced in the `Content-Disposition` header is the name of the file,
+/// or derived from the title of the paste for unnamed files. Passing `?download=true` marks the response as an attachment. Burn-after-reading pastes are not served,
+/// since link previews would destroy them. They have to be read via [`burn_raw_paste`] instead.
+///
+/// Content that is stored compressed is served as it is if the client accepts the codec via `Accept-Encoding`.
+/// Password-protected pastes need an unlock cookie or their password in the `X-Paste-Password` header.
+#[tracing::instrument(name = "Raw Paste Request", skip(req, user, pool, store, pepper))]
+pub async fn raw_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    query: web::Query<RawQuery>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    match Paste::fetch_metadata(&short_id, viewer, &pool).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => locked_response(&paste),
+        Ok(paste) if paste.burn_after_reading => HttpResponse::Forbidden()
+            .content_type(ContentType::plaintext())
+            .body(
+                "this paste is deleted after reading. \
+                send a POST request to this url to read it, e.g. `curl -X POST`\n",
+            ),
+        Ok(paste) => raw_response(paste, 0, query.download, Some((&req, store.get_ref()))).await,
+        Err(e) => raw_error_response(e),
+    }
+}
+
+/// Serve the content of a file of a paste, chosen by its name, as plain text.
+///
+/// Behaves like [`raw_paste`] otherwise. Burn-after-reading pastes can only be read as a whole.
+#[tracing::instrument(name = "Raw Paste File Request", skip(req, user, pool, store, pepper))]
+pub async fn raw_paste_file(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<(String, String)>,
+    query: web::Query<RawQuery>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let (short_id, filename) = path.into_inner();
+
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    match Paste::fetch_metadata(&short_id, viewer, &pool).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => locked_response(&paste),
+        Ok(paste) if paste.burn_after_reading => HttpResponse::Forbidden()
+            .content_type(ContentType::plaintext())
+            .body(
+                "this paste is deleted after reading. \
+                download it as a whole via POST, e.g. `curl -X POST .../download.tar.gz`\n",
+            ),
+        Ok(paste) => match paste
+            .files
+            .iter()
+            .position(|file| file.filename.as_deref() == Some(&filename))
+        {
+            Some(index) => {
+                let passthrough = Some((&req, store.get_ref()));
+                raw_response(paste, index, query.download, passthrough).await
+            }
+            None => HttpResponse::NotFound()
+                .content_type(ContentType::plaintext())
+                .body("file not found\n"),
+        },
+        Err(e) => raw_error_response(e),
+    }
+}
+
+/// Serve the content of a burn-after-reading paste as plain text and delete it.
+///
+/// Pastes that are not burn-after-reading are served like in [`raw_paste`].
+/// Burn-after-reading pastes with several files are kept, they have to be downloaded as a whole.
+/// Their content is always served uncompressed, since its blob is gone once the paste is deleted.
+#[tracing::instrument(name = "Raw Paste Burn Request", skip(req, user, pool, store, pepper))]
+pub async fn burn_raw_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    query: web::Query<RawQuery>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => {
+            return locked_response(&paste);
+        }
+        Ok(paste) if paste.burn_after_reading && paste.files.len() > 1 => {
+            return HttpResponse::Conflict()
+                .content_type(ContentType::plaintext())
+                .body(
+                    "this paste holds several files and is deleted after reading. \
+                    download it as a whole via POST, e.g. `curl -X POST .../download.tar.gz`\n",
+                );
+        }
+        Ok(paste) if paste.burn_after_reading => {
+            Paste::burn(&paste.id, viewer, &pool, store.get_ref()).await
+        }
+        result => result,
+    };
+
+    match paste {
+        Ok(paste) => raw_response(paste, 0, query.download, None).await,
+        Err(e) => raw_error_response(e),
+    }
+}
+
+/// Build the plain text response carrying the content of the file at `index` of `paste`.
+///
+/// * `passthrough` - request and [`BlobStore`] to load the content of a paste that was fetched without it.
+///   The content is served as it is stored if the client accepts its codec. `None` if the content is loaded already
+async fn raw_response(
+    mut paste: Paste,
+    index: usize,
+    download: bool,
+    passthrough: Option<(&HttpRequest, &dyn BlobStore)>,
+) -> HttpResponse {
+    // pastes may only consist of attachments
+    if index >= paste.files.len() {
+        return HttpResponse::NotFound()
+            .content_type(ContentType::plaintext())
+            .body("file not found\n");
+    }
+    let file = paste.files.swap_remove(index);
+
+    let disposition = ContentDisposition {
+        disposition: if download {
+            DispositionType::Attachment
+        } else {
+            DispositionType::Inline
+        },
+        parameters: disposition_filename(&raw_filename(&paste, &file)),
+    };
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(ContentType::plaintext())
+        .insert_header(disposition);
+    let Some((req, store)) = passthrough else {
+        return response.body(file.content);
+    };
+
+    response.insert_header((VARY, "Accept-Encoding"));
+    let accepted = req
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match file
+        .load_encoded(store, |encoding| accepts_encoding(accepted, encoding))
+        .await
+    {
+        Ok((Some(encoding), data)) => response
+            .insert_header((CONTENT_ENCODING, encoding))
+            .body(data),
+        Ok((None, data)) => response.body(data),
+        Err(e) => {
+            tracing::error!("failed to load content of a paste file: {:?}", e);
+            HttpResponse::InternalServerError()
+                .content_type(ContentType::plaintext())
+                .body("error while processing request\n")
+        }
+    }
+}
+
+/// Whether an `Accept-Encoding` header value allows `encoding`. Encodings with `q=0` are refused.
+fn accepts_encoding(header: &str, encoding: &str) -> bool {
+    header.split(',').any(|item| {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let refused = params.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                == Some(0.0)
+        });
+
+        (name.eq_ignore_ascii_case(encoding) || name == "*") && !refused
+    })
+}
+
+/// Translate an error returned by [`Paste`] operations into a plain text response.
+pub(super) fn raw_error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref() {
+        Some(PasteError::PasteNotFound) => HttpResponse::NotFound()
+            .content_type(ContentType::plaintext())
+            .body("paste not found\n"),
+        _ => {
+            tracing::error!("error while processing raw paste request: {:?}", e);
+            HttpResponse::InternalServerError()
+                .content_type(ContentType::plaintext())
+                .body("error while processing request\n")
+        }
+    }
+}
+
+/// Determine the filename under which a file of a paste should be saved.
+///
+/// Uses the name of the file or else the title of the paste with path separators and control characters removed,
+/// falling back to `{short_id}.txt` for pastes without a usable title.
+fn raw_filename(paste: &Paste, file: &PasteFile) -> String {
+    let title = file
+        .filename
+        .as_deref()
+        .or(paste.title.as_deref())
+        .unwrap_or_default();
+    let filename: String = title
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '/' | '\\' | '"'))
+        .collect();
+    let filename = filename.trim().trim_start_matches('.');
+
+    if filename.is_empty() {
+        format!("{}.txt", paste.short_id)
+    } else {
+        filename.to_owned()
+    }
+}
+
+/// Build the `filename` parameters for a `Content-Disposition` header.
+///
+/// Non-ASCII filenames are sent as RFC 5987 extended value, with an ASCII fallback for older clients.
+pub(super) fn disposition_filename(filename: &str) -> Vec<DispositionParam> {
+    if filename.is_ascii() {
+        return vec![DispositionParam::Filename(filename.to_owned())];
+    }
+
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    vec![
+        DispositionParam::Filename(fallback),
+        DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".to_owned()),
+            language_tag: None,
+            value: filename.as_bytes().to_vec(),
+        }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{PasteKind, Visibility};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn paste_with_title(title: Option<&str>) -> Paste {
+        Paste {
+            id: Uuid::nil(),
+            short_id: Uuid::nil().to_string(),
+            slug: None,
+            owner: None,
+            title: title.map(str::to_owned),
+            files: vec![PasteFile {
+                filename: None,
+                content: String::new(),
+                language: None,
+                language_confidence: None,
+                blob: None,
+            }],
+            revision: 1,
+            created_at: Utc::now(),
+            expires_at: None,
+            burn_after_reading: false,
+            visibility: Visibility::Unlisted,
+            forked_from: None,
+            password_hash: None,
+            kind: PasteKind::Plain,
+        }
+    }
+
+    #[test]
+    fn filename_from_title() {
+        let paste = paste_with_title(Some("deploy.sh"));
+
+        assert_eq!(raw_filename(&paste, &paste.files[0]), "deploy.sh");
+    }
+
+    #[test]
+    fn filename_of_named_file() {
+        let mut paste = paste_with_title(Some("deploy.sh"));
+        paste.files[0].filename = Some("Cargo.toml".into());
+
+        assert_eq!(raw_filename(&paste, &paste.files[0]), "Cargo.toml");
+    }
+
+    #[test]
+    fn filename_strips_path_separators() {
+        let paste = paste_with_title(Some("../../etc/passwd"));
+
+        assert_eq!(raw_filename(&paste, &paste.files[0]), "etcpasswd");
+    }
+
+    #[test]
+    fn filename_falls_back_to_id() {
+        let paste = paste_with_title(None);
+
+        assert_eq!(
+            raw_filename(&paste, &paste.files[0]),
+            "00000000-0000-0000-0000-000000000000.txt"
+        );
+    }
+
+    #[test]
+    fn accepts_listed_encodings() {
+        assert!(accepts_encoding("gzip, deflate, br, zstd", "zstd"));
+        assert!(accepts_encoding("deflate, GZIP;q=0.5", "gzip"));
+        assert!(accepts_encoding("*", "gzip"));
+        assert!(!accepts_encoding("gzip, deflate", "zstd"));
+        assert!(!accepts_encoding("zstd;q=0, gzip", "zstd"));
+        assert!(!accepts_encoding("", "gzip"));
+    }
+
+    #[test]
+    fn non_ascii_filename_has_fallback() {
+        let params = disposition_filename("grüße.txt");
+
+        assert_eq!(params[0], DispositionParam::Filename("gr__e.txt".into()));
+        assert!(matches!(params[1], DispositionParam::FilenameExt(_)));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::diff::{diff_lines, Hunk};
+use crate::entity::{is_short_id, Paste, PasteFile, PasteRevision, Scope};
+use crate::routes::session::{CurrentUser, OptionalUser};
+use crate::setup::Pepper;
+use crate::storage::BlobStore;
+use actix_web::{
+    web::{self, Redirect},
+    Either, HttpRequest,
+};
+use actix_web_lab::respond::Html;
+use askama::Template;
+use sqlx::PgPool;
+
+use super::unlock::is_unlocked;
+use super::view::not_found;
+
+/// Unchanged lines shown around every change of a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Revision as it is shown in the revision history.
+struct RevisionEntry {
+    revision: i32,
+    title: String,
+    created_at: String,
+    files: usize,
+    /// Lines of all files together
+    lines: usize,
+}
+
+#[derive(Template)]
+#[template(path = "revisions.html")]
+struct RevisionsPage<'a> {
+    id: &'a str,
+    title: &'a str,
+    /// Number of the latest revision
+    latest: i32,
+    /// All revisions, newest first
+    revisions: Vec<RevisionEntry>,
+}
+
+/// Layout of a diff.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffView {
+    /// Changes below each other, as produced by `diff -u`
+    #[default]
+    Unified,
+    /// Old and new version next to each other
+    Split,
+}
+
+impl DiffView {
+    /// Return a string representation of the enum value.
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Unified => "unified",
+            Self::Split => "split",
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "diff.html")]
+struct DiffPage<'a> {
+    id: &'a str,
+    title: &'a str,
+    old: &'a PasteRevision,
+    new: &'a PasteRevision,
+    /// Numbers of all revisions of the paste, to pick others to compare
+    revisions: Vec<i32>,
+    /// Files that differ between both revisions
+    files: Vec<FileDiff<'a>>,
+    split: bool,
+}
+
+impl<'a> DiffPage<'a> {
+    fn old_title(&self) -> &str {
+        self.old.title.as_deref().unwrap_or("Untitled paste")
+    }
+
+    fn new_title(&self) -> &str {
+        self.new.title.as_deref().unwrap_or("Untitled paste")
+    }
+}
+
+/// Changes to a single file between two revisions.
+struct FileDiff<'a> {
+    /// `None` if the file was added
+    old: Option<&'a PasteFile>,
+    /// `None` if the file was removed
+    new: Option<&'a PasteFile>,
+    hunks: Vec<Hunk<'a>>,
+}
+
+impl<'a> FileDiff<'a> {
+    fn new(old: Option<&'a PasteFile>, new: Option<&'a PasteFile>) -> Self {
+        let content = |file: Option<&'a PasteFile>| file.map_or("", |file| file.content.as_str());
+        Self {
+            old,
+            new,
+            hunks: diff_lines(content(old), content(new), DIFF_CONTEXT),
+        }
+    }
+
+    fn filename(&self) -> Option<&str> {
+        self.new
+            .or(self.old)
+            .and_then(|file| file.filename.as_deref())
+    }
+
+    /// Describe how the file as a whole changed, if it was added, removed or renamed.
+    fn status(&self) -> Option<String> {
+        match (self.old, self.new) {
+            (None, _) => Some("added".to_owned()),
+            (_, None) => Some("removed".to_owned()),
+            (Some(old), Some(new)) if old.filename != new.filename => Some(format!(
+                "renamed from {}",
+                old.filename.as_deref().unwrap_or("unnamed file")
+            )),
+            _ => None,
+        }
+    }
+
+    /// Old and new language, if the language changed.
+    fn language_change(&self) -> Option<(&str, &str)> {
+        let language = |file: &'a PasteFile| file.language.as_deref().unwrap_or("plain text");
+        match (self.old, self.new) {
+            (Some(old), Some(new)) if old.language != new.language => {
+                Some((language(old), language(new)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Pair up the files of two revisions of a paste, in the order of the newer revision.
+///
+/// Files are matched by name. If exactly one file on each side is left over, it is treated as renamed.
+/// Other files are added or removed.
+fn pair_files<'a>(
+    old: &'a [PasteFile],
+    new: &'a [PasteFile],
+) -> Vec<(Option<&'a PasteFile>, Option<&'a PasteFile>)> {
+    let matching = |file: &PasteFile| old.iter().find(|o| o.filename == file.filename);
+    let unmatched_old: Vec<_> = old
+        .iter()
+        .filter(|o| !new.iter().any(|file| file.filename == o.filename))
+        .collect();
+    let unmatched_new = new.iter().filter(|file| matching(file).is_none()).count();
+    let renamed = match (unmatched_old.as_slice(), unmatched_new) {
+        ([renamed], 1) => Some(*renamed),
+        _ => None,
+    };
+
+    let mut pairs: Vec<_> = new
+        .iter()
+        .map(|file| (matching(file).or(renamed), Some(file)))
+        .collect();
+    if renamed.is_none() {
+        pairs.extend(unmatched_old.into_iter().map(|file| (Some(file), None)));
+    }
+    pairs
+}
+
+#[derive(serde::Deserialize)]
+pub struct DiffQuery {
+    #[serde(default)]
+    view: DiffView,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DiffSelection {
+    a: i32,
+    b: i32,
+    #[serde(default)]
+    view: DiffView,
+}
+
+/// Fetch a paste whose revisions may be shown.
+///
+/// Burn-after-reading pastes are redirected to their confirmation page, so their content is not leaked.
+/// The same goes for password-protected pastes that have not been unlocked, their page asks for the password.
+async fn fetch_paste(
+    req: &HttpRequest,
+    user: Option<&CurrentUser>,
+    short_id: &str,
+    pool: &PgPool,
+    store: &dyn BlobStore,
+    pepper: &Pepper,
+) -> Result<Paste, Either<Html, Redirect>> {
+    match Paste::fetch(
+        short_id,
+        user.and_then(|user| user.id_for(Scope::PasteRead)),
+        pool,
+        store,
+    )
+    .await
+    {
+        Ok(paste) if paste.burn_after_reading || !is_unlocked(req, &paste, pepper).await => {
+            Err(Either::Right(Redirect::to(format!("/{}", paste.short_id))))
+        }
+        Ok(paste) => Ok(paste),
+        Err(e) => Err(not_found(e)),
+    }
+}
+
+#[tracing::instrument(name = "Paste Revisions Request", skip(req, user, pool, store, pepper))]
+pub async fn paste_revisions(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> Either<Html, Redirect> {
+    let paste = match fetch_paste(
+        &req,
+        user.as_ref(),
+        &short_id,
+        &pool,
+        store.get_ref(),
+        &pepper,
+    )
+    .await
+    {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+
+    let revisions = match PasteRevision::list(&paste.id, &pool, store.get_ref()).await {
+        Ok(revisions) => revisions,
+        Err(e) => return not_found(e),
+    };
+
+    let page = RevisionsPage {
+        id: &paste.short_id,
+        title: paste.title.as_deref().unwrap_or("Untitled paste"),
+        latest: paste.revision,
+        revisions: revisions
+            .into_iter()
+            .map(|revision| RevisionEntry {
+                revision: revision.revision,
+                title: revision
+                    .title
+                    .unwrap_or_else(|| "Untitled paste".to_owned()),
+                created_at: revision.created_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+                files: revision.files.len(),
+                lines: revision
+                    .files
+                    .iter()
+                    .map(|file| file.content.lines().count())
+                    .sum(),
+            })
+            .collect(),
+    };
+    Either::Left(Html(page.render().unwrap()))
+}
+
+/// Show the changes between revisions `a` and `b` of a paste.
+#[tracing::instrument(
+    name = "Paste Diff Request",
+    skip(req, user, pool, store, query, pepper)
+)]
+pub async fn paste_diff(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<(String, i32, i32)>,
+    query: web::Query<DiffQuery>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> Either<Html, Redirect> {
+    let (short_id, a, b) = path.into_inner();
+    let paste = match fetch_paste(
+        &req,
+        user.as_ref(),
+        &short_id,
+        &pool,
+        store.get_ref(),
+        &pepper,
+    )
+    .await
+    {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+
+    let (old, new) = match tokio::try_join!(
+        PasteRevision::fetch(&paste.id, a, &pool, store.get_ref()),
+        PasteRevision::fetch(&paste.id, b, &pool, store.get_ref())
+    ) {
+        Ok(revisions) => revisions,
+        Err(e) => return not_found(e),
+    };
+    let revisions = (1..=paste.revision).rev().collect();
+    let split = query.view == DiffView::Split;
+
+    // diffing large pastes takes a while, keep it off the async workers
+    let html = web::block(move || {
+        DiffPage {
+            id: &paste.short_id,
+            title: paste.title.as_deref().unwrap_or("Untitled paste"),
+            old: &old,
+            new: &new,
+            revisions,
+            files: pair_files(&old.files, &new.files)
+                .into_iter()
+                .filter(|(old, new)| old != new)
+                .map(|(old, new)| FileDiff::new(old, new))
+                .collect(),
+            split,
+        }
+        .render()
+    })
+    .await;
+
+    match html {
+        Ok(html) => Either::Left(Html(html.unwrap())),
+        Err(e) => not_found(e.into()),
+    }
+}
+
+/// Target of the form on the diff and revision pages, which redirects to the diff of the selected revisions.
+#[tracing::instrument(name = "Paste Diff Selection Request", skip(query))]
+pub async fn select_diff(id: web::Path<String>, query: web::Query<DiffSelection>) -> Redirect {
+    // the id ends up in the location, which must not turn into a link to another host
+    if !is_short_id(&id) {
+        return Redirect::to("/notfound");
+    }
+    Redirect::to(format!(
+        "/{}/diff/{}/{}?view={}",
+        id,
+        query.a,
+        query.b,
+        query.view.as_str()
+    ))
+    .see_other()
+}
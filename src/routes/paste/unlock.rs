@@ -0,0 +1,256 @@
+use crate::auth::{sign, verify_signature};
+use crate::entity::{Paste, Scope};
+use crate::routes::session::OptionalUser;
+use crate::setup::Pepper;
+use crate::storage::BlobStore;
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_lab::respond::Html;
+use askama::Template;
+use chrono::{Duration, Utc};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use super::raw::raw_error_response;
+
+/// Cookie that marks a password-protected paste as unlocked. Scoped to the path of the paste.
+const UNLOCK_COOKIE: &str = "pastr_unlock";
+
+/// Header that carries the password of a paste for clients that do not keep cookies, like `curl`.
+pub(crate) const PASSWORD_HEADER: &str = "X-Paste-Password";
+
+/// Minutes a viewer is not asked for the password again after unlocking a paste.
+const UNLOCK_MINUTES: i64 = 30;
+
+#[derive(Template)]
+#[template(path = "unlock.html")]
+struct UnlockPage<'a> {
+    id: &'a str,
+    title: &'a str,
+    /// The previously entered password was wrong
+    failed: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnlockForm {
+    password: String,
+}
+
+/// Check the password entered on the prompt page and remember the unlocked paste in a signed cookie.
+///
+/// The cookie expires after [`UNLOCK_MINUTES`] and is only sent along with requests below the path of the paste,
+/// so its raw view, downloads and attachments are unlocked as well. Wrong passwords show the prompt again.
+#[tracing::instrument(name = "Paste Unlock Request", skip(user, form, pool, store, pepper))]
+pub async fn unlock_paste(
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    form: web::Form<UnlockForm>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) => paste,
+        Err(e) => return raw_error_response(e),
+    };
+
+    let pepper = pepper.0.expose_secret().as_bytes();
+    match paste.verify_password(&form.password, pepper.to_vec()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Unauthorized()
+                .content_type(ContentType::html())
+                .body(render_unlock_page(&paste, true));
+        }
+        Err(e) => return raw_error_response(e),
+    }
+
+    HttpResponse::SeeOther()
+        .insert_header(("Location", format!("/{}", paste.short_id)))
+        .cookie(unlock_cookie(&paste, pepper))
+        .finish()
+}
+
+/// Signed cookie that unlocks `paste` for [`UNLOCK_MINUTES`]. Only sent over https.
+fn unlock_cookie(paste: &Paste, pepper: &[u8]) -> Cookie<'static> {
+    let expires = Utc::now() + Duration::minutes(UNLOCK_MINUTES);
+    Cookie::build(
+        UNLOCK_COOKIE,
+        unlock_token(paste, expires.timestamp(), pepper),
+    )
+    .path(format!("/{}", paste.short_id))
+    .max_age(time::Duration::minutes(UNLOCK_MINUTES))
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .secure(true)
+    .finish()
+}
+
+/// Whether the client sending `req` may see the content of `paste`.
+///
+/// Pastes without a password are always unlocked. Others need a valid unlock cookie
+/// or the correct password in the [`PASSWORD_HEADER`].
+pub(crate) async fn is_unlocked(req: &HttpRequest, paste: &Paste, pepper: &Pepper) -> bool {
+    if !paste.is_password_protected() {
+        return true;
+    }
+
+    let pepper = pepper.0.expose_secret().as_bytes();
+    if let Some(cookie) = req.cookie(UNLOCK_COOKIE) {
+        if is_valid_token(cookie.value(), paste, Utc::now().timestamp(), pepper) {
+            return true;
+        }
+    }
+
+    let Some(password) = req
+        .headers()
+        .get(PASSWORD_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    match paste.verify_password(password, pepper.to_vec()).await {
+        Ok(unlocked) => unlocked,
+        Err(e) => {
+            tracing::error!("failed to verify paste password: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Page asking for the password of `paste`, shown instead of its content.
+pub(super) fn unlock_page(paste: &Paste) -> Html {
+    Html(render_unlock_page(paste, false))
+}
+
+fn render_unlock_page(paste: &Paste, failed: bool) -> String {
+    UnlockPage {
+        id: &paste.short_id,
+        title: paste.title.as_deref().unwrap_or("Untitled paste"),
+        failed,
+    }
+    .render()
+    .unwrap()
+}
+
+/// Plain text response for requests to the content of a paste that is still locked.
+pub(super) fn locked_response(paste: &Paste) -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .content_type(ContentType::plaintext())
+        .body(format!(
+            "this paste is protected by a password. unlock it at /{}, \
+            or send the password in the {} header\n",
+            paste.short_id, PASSWORD_HEADER
+        ))
+}
+
+/// Value of the unlock cookie for `paste`, valid until the unix timestamp `expires`.
+///
+/// The signature covers the password hash, so changing the password locks the paste again.
+fn unlock_token(paste: &Paste, expires: i64, pepper: &[u8]) -> String {
+    format!(
+        "{}.{}",
+        expires,
+        sign(&unlock_message(paste, expires), pepper)
+    )
+}
+
+/// Whether `token` was issued by [`unlock_token`] for `paste` and has not expired at the unix timestamp `now`.
+fn is_valid_token(token: &str, paste: &Paste, now: i64, pepper: &[u8]) -> bool {
+    let Some((expires, signature)) = token.split_once('.') else {
+        return false;
+    };
+    let Ok(expires) = expires.parse::<i64>() else {
+        return false;
+    };
+
+    expires > now && verify_signature(&unlock_message(paste, expires), signature, pepper)
+}
+
+fn unlock_message(paste: &Paste, expires: i64) -> Vec<u8> {
+    format!(
+        "unlock:{}:{}:{}",
+        paste.id,
+        expires,
+        paste.password_hash.as_deref().unwrap_or_default()
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{PasteKind, Visibility};
+    use uuid::Uuid;
+
+    const PEPPER: &[u8] = b"long and bad pepper value - not use in production";
+
+    fn protected_paste(password_hash: &str) -> Paste {
+        Paste {
+            id: Uuid::new_v4(),
+            short_id: "aZ3kP9xQ".to_owned(),
+            slug: None,
+            owner: None,
+            title: None,
+            files: Vec::new(),
+            revision: 1,
+            created_at: Utc::now(),
+            expires_at: None,
+            burn_after_reading: false,
+            visibility: Visibility::Unlisted,
+            forked_from: None,
+            password_hash: Some(password_hash.to_owned()),
+            kind: PasteKind::Plain,
+        }
+    }
+
+    #[test]
+    fn unlock_cookie_is_limited_to_its_paste() {
+        let cookie = unlock_cookie(&protected_paste("hash"), PEPPER);
+
+        assert_eq!(cookie.path(), Some("/aZ3kP9xQ"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.secure(), Some(true));
+    }
+
+    #[test]
+    fn token_unlocks_its_paste_until_it_expires() {
+        let paste = protected_paste("hash");
+        let token = unlock_token(&paste, 1000, PEPPER);
+
+        assert!(is_valid_token(&token, &paste, 999, PEPPER));
+        assert!(!is_valid_token(&token, &paste, 1000, PEPPER));
+        assert!(!is_valid_token(
+            &token,
+            &protected_paste("hash"),
+            999,
+            PEPPER
+        ));
+    }
+
+    #[test]
+    fn token_is_invalidated_by_new_password() {
+        let mut paste = protected_paste("hash");
+        let token = unlock_token(&paste, 1000, PEPPER);
+        paste.password_hash = Some("other hash".to_owned());
+
+        assert!(!is_valid_token(&token, &paste, 999, PEPPER));
+    }
+
+    #[test]
+    fn tampered_token_is_rejected() {
+        let paste = protected_paste("hash");
+        let token = unlock_token(&paste, 1000, PEPPER);
+        let (_, signature) = token.split_once('.').unwrap();
+
+        assert!(!is_valid_token(
+            &format!("2000.{}", signature),
+            &paste,
+            999,
+            PEPPER
+        ));
+        assert!(!is_valid_token("1000", &paste, 999, PEPPER));
+    }
+}
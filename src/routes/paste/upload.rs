@@ -0,0 +1,286 @@
+use super::PASSWORD_HEADER;
+use crate::entity::{
+    Expiry, NewAttachment, NewFile, NewPaste, Paste, PasteError, PasteKind, Scope, Visibility,
+};
+use crate::routes::session::OptionalUser;
+use crate::setup::{AppBaseUrl, MaxAttachmentSize, MaxExpiry, MaxPasteSize, Pepper};
+use crate::sniff::{extension, is_text};
+use crate::storage::BlobStore;
+use actix_multipart::Multipart;
+use actix_web::http::header::{self, ContentType};
+use actix_web::web::{self, Bytes, BytesMut};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::Utc;
+use futures_util::{Stream, StreamExt};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct UploadQuery {
+    /// Requested lifetime. Capped by the configured maximum
+    expires: Option<Expiry>,
+    /// Delete the paste the first time it is read
+    #[serde(default)]
+    burn: bool,
+    /// Anonymous uploads can only be public or unlisted
+    #[serde(default)]
+    visibility: Visibility,
+}
+
+/// Content of an uploaded file together with the filename supplied by the client.
+struct Upload {
+    filename: Option<String>,
+    content: Bytes,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum UploadError {
+    #[error("paste exceeds the maximum size of {0} bytes")]
+    TooLarge(usize),
+    #[error("paste must not be empty")]
+    Empty,
+    #[error("malformed upload")]
+    Malformed(#[source] anyhow::Error),
+}
+
+/// Create a paste from a plain request body or a multipart upload and respond with its url.
+///
+/// Supports `curl -F 'f=@file.log' https://pastr/` as well as `cat file | curl --data-binary @- https://pastr/`.
+/// Every part of a multipart upload becomes a file of the paste, named after the uploaded file.
+/// Parts that are not text, like images or PDFs, are stored as attachments instead.
+/// The paste is titled after the first file.
+/// The response only consists of the url of the new paste, so it can be used directly in scripts.
+/// The lifetime of the paste can be chosen with `?expires=1h`, a password that viewers have to enter
+/// can be set via the `X-Paste-Password` header.
+#[tracing::instrument(name = "Paste Upload Request", skip_all)]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    query: web::Query<UploadQuery>,
+    payload: web::Payload,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    base_url: web::Data<AppBaseUrl>,
+    max_size: web::Data<MaxPasteSize>,
+    max_attachment_size: web::Data<MaxAttachmentSize>,
+    max_expiry: web::Data<MaxExpiry>,
+    pepper: web::Data<Pepper>,
+) -> HttpResponse {
+    if user
+        .as_ref()
+        .is_some_and(|user| !user.has_scope(Scope::PasteWrite))
+    {
+        return HttpResponse::Forbidden()
+            .content_type(ContentType::plaintext())
+            .body("api token lacks the paste:write scope\n");
+    }
+
+    let limit = max_size.0.max(max_attachment_size.0);
+    let uploads = match read_upload(&req, payload, limit).await {
+        Ok(uploads) => uploads,
+        Err(e) => return upload_error_response(e),
+    };
+
+    // the names of attachments have to outlive the paste built from them
+    let default_names: Vec<_> = uploads
+        .iter()
+        .map(|upload| format!("upload.{}", extension(&upload.content)))
+        .collect();
+
+    let mut files = Vec::new();
+    let mut attachments = Vec::new();
+    for (upload, default_name) in uploads.iter().zip(&default_names) {
+        let text = std::str::from_utf8(&upload.content)
+            .ok()
+            .filter(|_| is_text(&upload.content));
+        let Some(content) = text else {
+            if upload.content.len() > max_attachment_size.0 {
+                return upload_error_response(UploadError::TooLarge(max_attachment_size.0));
+            }
+            attachments.push(NewAttachment {
+                filename: upload.filename.as_deref().unwrap_or(default_name),
+                data: &upload.content,
+            });
+            continue;
+        };
+
+        if content.trim().is_empty() {
+            return upload_error_response(UploadError::Empty);
+        }
+        files.push(NewFile {
+            filename: upload.filename.as_deref(),
+            content,
+            language: None,
+        });
+    }
+
+    if files.iter().map(|file| file.content.len()).sum::<usize>() > max_size.0 {
+        return upload_error_response(UploadError::TooLarge(max_size.0));
+    }
+
+    let password = req
+        .headers()
+        .get(PASSWORD_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|password| !password.is_empty());
+    let password_hash = match password {
+        Some(password) => {
+            let pepper = pepper.0.expose_secret().as_bytes().to_vec();
+            match Paste::hash_password(password, pepper).await {
+                Ok(hash) => Some(hash),
+                Err(e) => {
+                    tracing::error!("error while hashing paste password: {:?}", e);
+                    return HttpResponse::InternalServerError()
+                        .content_type(ContentType::plaintext())
+                        .body("error while processing request\n");
+                }
+            }
+        }
+        None => None,
+    };
+
+    let paste = NewPaste {
+        owner: user.as_ref().map(|user| *user.id()),
+        title: uploads
+            .first()
+            .and_then(|upload| upload.filename.as_deref()),
+        files,
+        attachments,
+        expires_at: max_expiry.cap(query.expires).expires_at(Utc::now()),
+        burn_after_reading: query.burn,
+        visibility: query.visibility,
+        password_hash,
+        kind: PasteKind::Plain,
+        slug: None,
+    };
+
+    match Paste::create(&paste, &pool, store.get_ref()).await {
+        Ok(created) => HttpResponse::Created()
+            .content_type(ContentType::plaintext())
+            .body(format!("https://{}/{}\n", base_url.0, created.short_id)),
+        Err(e)
+            if matches!(
+                e.downcast_ref(),
+                Some(
+                    PasteError::PrivateWithoutOwner
+                        | PasteError::InvalidFiles
+                        | PasteError::InvalidAttachment
+                )
+            ) =>
+        {
+            HttpResponse::BadRequest()
+                .content_type(ContentType::plaintext())
+                .body(format!("{}\n", e))
+        }
+        Err(e) => {
+            tracing::error!("error while storing uploaded paste: {:?}", e);
+            HttpResponse::InternalServerError()
+                .content_type(ContentType::plaintext())
+                .body("error while processing request\n")
+        }
+    }
+}
+
+/// Read the uploaded files, either from the parts of a multipart form or from the plain body.
+///
+/// All files together may not exceed `max_size`.
+async fn read_upload(
+    req: &HttpRequest,
+    payload: web::Payload,
+    max_size: usize,
+) -> Result<Vec<Upload>, UploadError> {
+    let is_multipart = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+    if !is_multipart {
+        return Ok(vec![Upload {
+            filename: None,
+            content: read_limited(payload, max_size).await?,
+        }]);
+    }
+
+    let mut multipart = Multipart::new(req.headers(), payload);
+    let mut uploads = Vec::new();
+    let mut remaining = max_size;
+    while let Some(field) = multipart.next().await {
+        let field = field.map_err(|e| UploadError::Malformed(anyhow::anyhow!("{}", e)))?;
+        let filename = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_owned);
+
+        let content = read_limited(field, remaining).await.map_err(|e| match e {
+            UploadError::TooLarge(_) => UploadError::TooLarge(max_size),
+            e => e,
+        })?;
+        remaining -= content.len();
+        uploads.push(Upload { filename, content });
+    }
+
+    match uploads.is_empty() {
+        true => Err(UploadError::Empty),
+        false => Ok(uploads),
+    }
+}
+
+/// Collect a stream of bytes into memory, failing as soon as it grows beyond `max_size`.
+async fn read_limited<S, E>(mut stream: S, max_size: usize) -> Result<Bytes, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    let mut body = BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| UploadError::Malformed(anyhow::anyhow!("{}", e)))?;
+        if body.len() + chunk.len() > max_size {
+            return Err(UploadError::TooLarge(max_size));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+fn upload_error_response(e: UploadError) -> HttpResponse {
+    let mut response = match e {
+        UploadError::TooLarge(_) => HttpResponse::PayloadTooLarge(),
+        UploadError::Empty | UploadError::Malformed(_) => HttpResponse::BadRequest(),
+    };
+    response
+        .content_type(ContentType::plaintext())
+        .body(format!("{}\n", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+
+    fn chunks(parts: &[&'static str]) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Unpin {
+        stream::iter(
+            parts
+                .iter()
+                .map(|part| Ok(Bytes::from_static(part.as_bytes())))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[actix_web::test]
+    async fn read_within_limit() {
+        let body = read_limited(chunks(&["hello ", "world"]), 11)
+            .await
+            .expect("reading body failed");
+
+        assert_eq!(body, Bytes::from_static(b"hello world"));
+    }
+
+    #[actix_web::test]
+    async fn read_exceeding_limit() {
+        let result = read_limited(chunks(&["hello ", "world"]), 10).await;
+
+        assert!(matches!(result, Err(UploadError::TooLarge(10))));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::entity::{Paste, Scope};
+use crate::routes::session::OptionalUser;
+use actix_web::{
+    web::{self, Redirect},
+    Either, HttpRequest,
+};
+use actix_web_lab::respond::Html;
+use sqlx::PgPool;
+
+use super::view::not_found;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct VanityPath {
+    username: String,
+    slug: String,
+    /// Remainder of the path, like `raw` or `revisions`
+    tail: Option<String>,
+}
+
+/// Redirect the vanity url `/{username}/{slug}/...` of a paste to the same page under its short id.
+///
+/// The redirect is temporary, since owners can move their slug to another paste.
+#[tracing::instrument(name = "Vanity Url Request", skip(req, user, pool))]
+pub async fn vanity_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    path: web::Path<VanityPath>,
+    pool: web::Data<PgPool>,
+) -> Either<Html, Redirect> {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let short_id = match Paste::find_slug(&path.username, &path.slug, viewer, &pool).await {
+        Ok(short_id) => short_id,
+        Err(e) => return not_found(e),
+    };
+
+    let mut location = format!("/{}", short_id);
+    if let Some(tail) = path.tail.as_deref().filter(|tail| !tail.is_empty()) {
+        location.push('/');
+        location.push_str(tail);
+    }
+    if !req.query_string().is_empty() {
+        location.push('?');
+        location.push_str(req.query_string());
+    }
+
+    Either::Right(Redirect::to(location))
+}
@@ -0,0 +1,307 @@
+use super::encrypted::encrypted_page;
+use super::unlock::{is_unlocked, unlock_page};
+use crate::entity::{Attachment, ForkOrigin, Paste, PasteError, PasteFile, PasteKind, Scope};
+use crate::highlight::{find_syntax, highlight_lines, parse_line_range};
+use crate::routes::session::OptionalUser;
+use crate::setup::Pepper;
+use crate::sniff::is_inline_image;
+use crate::storage::BlobStore;
+use actix_web::{
+    web::{self, Redirect},
+    Either, HttpRequest,
+};
+use actix_web_lab::respond::Html;
+use askama::Template;
+use sqlx::PgPool;
+use std::ops::RangeInclusive;
+
+/// A line of a paste, rendered as highlighted html.
+struct Line {
+    number: usize,
+    html: String,
+    /// The line is part of the range selected via `?lines=`
+    selected: bool,
+}
+
+/// A file of a paste, rendered as highlighted lines.
+struct FileView<'a> {
+    /// Position of the file within the paste, starting at 1
+    number: usize,
+    filename: Option<&'a str>,
+    language: &'a str,
+    /// The language was detected automatically
+    detected: bool,
+    lines: Vec<Line>,
+}
+
+impl<'a> FileView<'a> {
+    fn new(
+        number: usize,
+        file: &'a PasteFile,
+        title: Option<&str>,
+        selected: Option<&RangeInclusive<usize>>,
+    ) -> Self {
+        let syntax = find_syntax(file.language.as_deref(), file.filename.as_deref().or(title));
+        let plain_text = syntax.name == "Plain Text";
+        let lines = highlight_lines(&file.content, syntax)
+            .into_iter()
+            .enumerate()
+            .map(|(i, html)| Line {
+                number: i + 1,
+                html,
+                selected: selected.is_some_and(|range| range.contains(&(i + 1))),
+            })
+            .collect();
+
+        Self {
+            number,
+            filename: file.filename.as_deref(),
+            language: match file.language.as_deref() {
+                Some(language) if plain_text => language,
+                _ if plain_text => "plain text",
+                _ => &syntax.name,
+            },
+            detected: file.language_confidence.is_some(),
+            lines,
+        }
+    }
+
+    /// Prefix of the line anchors. Empty for the first file, so links into single file pastes stay short.
+    fn anchor(&self) -> String {
+        match self.number {
+            1 => String::new(),
+            number => format!("F{}-", number),
+        }
+    }
+
+    /// Query parameters that select this file for `?lines=`.
+    fn query(&self) -> String {
+        match self.number {
+            1 => String::new(),
+            number => format!("file={}&", number),
+        }
+    }
+}
+
+/// An attachment of a paste, shown as image or offered as download.
+struct AttachmentView {
+    filename: String,
+    mime_type: String,
+    /// Human readable size, like `1.5 MiB`
+    size: String,
+    /// Raster images are shown on the page
+    inline: bool,
+}
+
+impl From<Attachment> for AttachmentView {
+    fn from(attachment: Attachment) -> Self {
+        Self {
+            inline: is_inline_image(&attachment.mime_type),
+            size: format_size(attachment.size),
+            filename: attachment.filename,
+            mime_type: attachment.mime_type,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "paste.html")]
+struct PastePage<'a> {
+    id: &'a str,
+    title: &'a str,
+    files: Vec<FileView<'a>>,
+    attachments: Vec<AttachmentView>,
+    revision: i32,
+    forked_from: Option<ForkOrigin>,
+    created_at: String,
+    expires_at: Option<String>,
+    /// The paste was deleted while rendering this page
+    burned: bool,
+}
+
+impl<'a> PastePage<'a> {
+    /// * `selected` - number of a file, starting at 1, and the range of its lines to highlight
+    fn new(
+        paste: &'a Paste,
+        attachments: Vec<Attachment>,
+        burned: bool,
+        selected: Option<(usize, RangeInclusive<usize>)>,
+    ) -> Self {
+        let files = paste
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let lines = selected
+                    .as_ref()
+                    .filter(|(number, _)| *number == i + 1)
+                    .map(|(_, lines)| lines);
+                FileView::new(i + 1, file, paste.title.as_deref(), lines)
+            })
+            .collect();
+
+        Self {
+            id: &paste.short_id,
+            title: paste.title.as_deref().unwrap_or("Untitled paste"),
+            files,
+            attachments: attachments.into_iter().map(AttachmentView::from).collect(),
+            revision: paste.revision,
+            forked_from: paste.forked_from.clone(),
+            created_at: paste.created_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+            expires_at: paste
+                .expires_at
+                .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string()),
+            burned,
+        }
+    }
+}
+
+/// Confirmation page shown instead of burn-after-reading pastes,
+/// so that link previews and crawlers do not destroy them.
+#[derive(Template)]
+#[template(path = "burn.html")]
+struct BurnConfirmationPage<'a> {
+    id: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ViewQuery {
+    /// Number of the file that `lines` refers to, starting at 1
+    file: Option<usize>,
+    /// Range of lines to highlight, e.g. `L10-L20`
+    lines: Option<String>,
+}
+
+/// Show a paste. Password-protected pastes ask for their password first.
+#[tracing::instrument(
+    name = "Paste Page Request",
+    skip(req, user, pool, store, query, pepper)
+)]
+pub async fn view_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    query: web::Query<ViewQuery>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> Either<Html, Redirect> {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let paste = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) => paste,
+        Err(e) => return not_found(e),
+    };
+
+    if !is_unlocked(&req, &paste, &pepper).await {
+        return Either::Left(unlock_page(&paste));
+    }
+
+    if paste.burn_after_reading {
+        let html = BurnConfirmationPage {
+            id: &paste.short_id,
+        }
+        .render()
+        .unwrap();
+        return Either::Left(Html(html));
+    }
+
+    let selected = query
+        .lines
+        .as_deref()
+        .and_then(parse_line_range)
+        .map(|lines| (query.file.unwrap_or(1), lines));
+    let attachments = match Attachment::list(&paste.id, &pool).await {
+        Ok(attachments) => attachments,
+        Err(e) => return not_found(e),
+    };
+    render_paste(paste, attachments, false, selected).await
+}
+
+/// Show a burn-after-reading paste once and delete it.
+///
+/// Target of the form on the confirmation page. Other pastes, and pastes that are still locked
+/// by their password, are redirected to their regular page.
+#[tracing::instrument(name = "Paste Burn Request", skip(req, user, pool, store, pepper))]
+pub async fn burn_paste(
+    req: HttpRequest,
+    OptionalUser(user): OptionalUser,
+    short_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    store: web::Data<dyn BlobStore>,
+    pepper: web::Data<Pepper>,
+) -> Either<Html, Redirect> {
+    let viewer = user.as_ref().and_then(|user| user.id_for(Scope::PasteRead));
+    let id = match Paste::fetch(&short_id, viewer, &pool, store.get_ref()).await {
+        Ok(paste) if !is_unlocked(&req, &paste, &pepper).await => {
+            return Either::Right(Redirect::to(format!("/{}", paste.short_id)).see_other());
+        }
+        Ok(paste) => paste.id,
+        Err(e) => return not_found(e),
+    };
+
+    match Paste::burn(&id, viewer, &pool, store.get_ref()).await {
+        // burn-after-reading pastes can not have attachments
+        Ok(paste) => render_paste(paste, Vec::new(), true, None).await,
+        Err(e) => match e.downcast_ref::<PasteError>() {
+            Some(PasteError::PasteNotFound) => {
+                Either::Right(Redirect::to(format!("/{}", short_id)).see_other())
+            }
+            _ => not_found(e),
+        },
+    }
+}
+
+/// Render the page of a paste on the blocking thread pool, since highlighting large pastes takes a while.
+///
+/// Encrypted pastes are rendered right away, they are decrypted and shown by the browser.
+async fn render_paste(
+    paste: Paste,
+    attachments: Vec<Attachment>,
+    burned: bool,
+    selected: Option<(usize, RangeInclusive<usize>)>,
+) -> Either<Html, Redirect> {
+    if paste.kind == PasteKind::Encrypted {
+        return Either::Left(encrypted_page(&paste, burned));
+    }
+
+    let render = move || PastePage::new(&paste, attachments, burned, selected).render();
+    match web::block(render).await {
+        Ok(html) => Either::Left(Html(html.unwrap())),
+        Err(e) => not_found(e.into()),
+    }
+}
+
+/// Format a size in bytes with a binary unit, e.g. `1.5 MiB`.
+fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+pub(super) fn not_found(e: anyhow::Error) -> Either<Html, Redirect> {
+    if e.downcast_ref::<PasteError>().is_none() {
+        tracing::error!("error while fetching paste: {:?}", e);
+    }
+    Either::Right(Redirect::to("/notfound"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(10 * 1024 * 1024), "10.0 MiB");
+    }
+}
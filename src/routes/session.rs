@@ -0,0 +1,188 @@
+use crate::entity::{Scope, User, SESSION_DAYS};
+use crate::routes::api::ApiResponse;
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Cookie that holds the token of a login session.
+pub(crate) const SESSION_COOKIE: &str = "pastr_session";
+
+/// The logged in user that sent a request, identified by an api token in the `Authorization: Bearer` header or by
+/// the session cookie.
+///
+/// Extracting it fails with `401 Unauthorized` for anonymous requests.
+/// Handlers that serve anonymous users as well take an [`OptionalUser`].
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    user: User,
+    /// Scopes of the api token of the request, `None` for sessions, which may do everything
+    scopes: Option<Vec<Scope>>,
+}
+
+impl CurrentUser {
+    /// UUID of the user, to be passed as owner or viewer of pastes.
+    pub fn id(&self) -> &Uuid {
+        self.user.id()
+    }
+
+    /// UUID of the user if the request may act as the user for `scope`, `None` if its api token lacks the scope.
+    pub fn id_for(&self, scope: Scope) -> Option<&Uuid> {
+        self.has_scope(scope).then(|| self.id())
+    }
+
+    /// Check whether the request may act as the user for `scope`. Always true for sessions.
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+
+    /// Check whether the request was authenticated by the session cookie rather than an api token.
+    pub fn is_session(&self) -> bool {
+        self.scopes.is_none()
+    }
+
+    /// Look up the user of a request.
+    ///
+    /// Resolves to `Ok(None)` for anonymous requests. An api token that is unknown or expired is an error rather
+    /// than anonymous, so scripts do not silently create pastes without an owner.
+    fn authenticate(
+        req: &HttpRequest,
+    ) -> LocalBoxFuture<'static, Result<Option<Self>, actix_web::Error>> {
+        let bearer = bearer_token(req).map(str::to_owned);
+        let session = req.cookie(SESSION_COOKIE);
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+        Box::pin(async move {
+            let Some(pool) = pool else {
+                return Ok(None);
+            };
+
+            if let Some(token) = bearer {
+                return match User::from_api_token(&token, &pool).await {
+                    Ok(Some((user, scopes))) => Ok(Some(Self {
+                        user,
+                        scopes: Some(scopes),
+                    })),
+                    Ok(None) => Err(unauthorized("invalid or expired api token")),
+                    Err(e) => {
+                        tracing::error!("error while looking up api token: {:?}", e);
+                        Err(ErrorInternalServerError("error while processing request"))
+                    }
+                };
+            }
+
+            let Some(session) = session else {
+                return Ok(None);
+            };
+            match User::from_session(session.value(), &pool).await {
+                Ok(user) => Ok(user.map(|user| Self { user, scopes: None })),
+                Err(e) => {
+                    tracing::error!("error while looking up session: {:?}", e);
+                    Err(ErrorInternalServerError("error while processing request"))
+                }
+            }
+        })
+    }
+}
+
+impl FromRequest for CurrentUser {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = Self::authenticate(req);
+
+        Box::pin(async move { user.await?.ok_or_else(|| unauthorized("login required")) })
+    }
+}
+
+/// The user that sent a request, or `None` for anonymous requests.
+///
+/// Unlike `Option<CurrentUser>`, extracting it fails with `401 Unauthorized` for invalid api tokens instead of
+/// treating the request as anonymous.
+#[derive(Debug, Clone)]
+pub struct OptionalUser(pub Option<CurrentUser>);
+
+impl FromRequest for OptionalUser {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = CurrentUser::authenticate(req);
+
+        Box::pin(async move { user.await.map(Self) })
+    }
+}
+
+/// Retrieve the api token of the `Authorization: Bearer` header, if there is one.
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+fn unauthorized(message: &'static str) -> actix_web::Error {
+    InternalError::from_response(
+        message,
+        HttpResponse::Unauthorized().json(ApiResponse::new(false, message)),
+    )
+    .into()
+}
+
+/// Cookie that carries the token of a new session to the browser. Expires together with the session.
+/// Only sent over https.
+pub(crate) fn session_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, token)
+        .path("/")
+        .max_age(time::Duration::days(SESSION_DAYS))
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(true)
+        .finish()
+}
+
+/// Cookie that makes the browser forget its session.
+pub(crate) fn removal_cookie() -> Cookie<'static> {
+    let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn session_cookie_is_not_readable_by_scripts() {
+        let cookie = session_cookie("token".to_owned());
+
+        assert_eq!(cookie.value(), "token");
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(removal_cookie().max_age(), Some(time::Duration::ZERO));
+    }
+
+    #[test]
+    fn bearer_token_is_read_from_authorization_header() {
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, "bearer pastr_abc "))
+            .to_http_request();
+        assert_eq!(bearer_token(&req), Some("pastr_abc"));
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, "Basic dXNlcjpwYXNz"))
+            .to_http_request();
+        assert_eq!(bearer_token(&req), None);
+    }
+}
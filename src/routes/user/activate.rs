@@ -6,7 +6,6 @@ use actix_web::{
 use actix_web_lab::respond::Html;
 use askama::Template;
 use sqlx::PgPool;
-use uuid::Uuid;
 
 #[derive(Template)]
 #[template(path = "activated.html")]
@@ -14,16 +13,17 @@ struct ActivationPage;
 
 #[derive(serde::Deserialize, Debug)]
 pub struct ActivationQuery {
-    pub user_id: Option<Uuid>,
+    /// Token from the link in the activation mail
+    pub token: Option<String>,
 }
 
-#[tracing::instrument(name = "Account Activation Request", skip(pool))]
+#[tracing::instrument(name = "Account Activation Request", skip(query, pool))]
 pub async fn activate_user(
-    user_id: web::Query<ActivationQuery>,
+    query: web::Query<ActivationQuery>,
     pool: web::Data<PgPool>,
 ) -> Either<Html, Redirect> {
-    match user_id.0.user_id {
-        Some(id) => match User::activate(&id, &pool).await {
+    match query.0.token {
+        Some(token) => match User::activate(&token, &pool).await {
             Ok(_) => Either::Left(Html(ActivationPage.render().unwrap())),
             Err(_) => Either::Right(Redirect::to("/notfound")),
         },
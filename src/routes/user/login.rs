@@ -0,0 +1,12 @@
+use actix_web_lab::respond::Html;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginPage;
+
+/// Login form, which logs in via `POST /api/login` and keeps the session in a cookie.
+pub async fn login() -> Html {
+    let html = LoginPage.render().unwrap();
+    Html(html)
+}
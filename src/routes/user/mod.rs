@@ -1,5 +1,9 @@
 mod activate;
+mod login;
 mod register;
+mod reset;
 
 pub use activate::activate_user;
+pub use login::login;
 pub use register::register;
+pub use reset::reset;
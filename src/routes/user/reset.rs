@@ -0,0 +1,25 @@
+use actix_web::web;
+use actix_web_lab::respond::Html;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "reset.html")]
+struct ResetPage {
+    token: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetQuery {
+    /// Token from the link in the reset mail
+    token: Option<String>,
+}
+
+/// Form to request a password reset link or, when opened from that link, to choose a new password.
+pub async fn reset(query: web::Query<ResetQuery>) -> Html {
+    let html = ResetPage {
+        token: query.0.token,
+    }
+    .render()
+    .unwrap();
+    Html(html)
+}
@@ -1,11 +1,27 @@
-use crate::config::{Config, DatabaseConfig};
+use crate::config::{AppConfig, Config, DatabaseConfig};
+use crate::entity::Expiry;
 use crate::log;
+use crate::reaper::reap_expired_pastes;
 use crate::routes::api::json_deserialize_error_handler;
-use crate::routes::api::user::register_user;
+use crate::routes::api::paste::{
+    add_attachment, create_paste, delete_attachment, delete_paste, fork_paste, get_paste,
+    list_forks, list_pastes, update_paste,
+};
+use crate::routes::api::user::{
+    create_token, delete_token, forgot_password, list_tokens, login_user, logout_user,
+    register_user, resend_activation, reset_password,
+};
 use crate::routes::healthcheck::health_check;
 use crate::routes::index::index_page;
 use crate::routes::not_found;
-use crate::routes::user::{activate_user, register};
+use crate::routes::paste::{
+    burn_download_paste, burn_paste, burn_raw_paste, create_fork, download_paste, forks_page,
+    new_encrypted_paste, paste_diff, paste_revisions, raw_paste, raw_paste_file, select_diff,
+    unlock_paste, upload_paste, vanity_paste, view_attachment, view_paste,
+};
+use crate::routes::user::{activate_user, login, register, reset};
+use crate::storage::{self, BlobStore};
+use crate::tcp::{ConnectionLimits, TcpPasteServer};
 use actix_files::Files;
 use actix_web::web::Data;
 use actix_web::{dev::Server, HttpServer};
@@ -13,12 +29,20 @@ use actix_web::{web, App};
 use secrecy::Secret;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 use tracing_actix_web::TracingLogger;
 
 /// Container for the Actix Application
 pub struct Application {
     pub port: u16,
+    /// Port of the tcp paste listener, if it is enabled
+    pub tcp_port: Option<u16>,
     actix_server: Server,
+    tcp_server: Option<TcpPasteServer>,
+    reaper: JoinHandle<()>,
 }
 
 impl Application {
@@ -28,20 +52,70 @@ impl Application {
     /// Returns an error if there are network related issues.
     pub async fn with_config(config: Config) -> Result<Self, anyhow::Error> {
         let db_pool = get_database_pool(config.database);
+        let store = storage::connect(&config.storage, config.compression, db_pool.clone())?;
         let address = format!("127.0.0.1:{}", config.app.port);
-        let sengrid_key = config.app.sendgrid_key;
-        let base_url = config.app.base_url;
         let socket = TcpListener::bind(address)?;
         let port = socket.local_addr()?.port();
-        let server = run(socket, db_pool, config.app.pepper, sengrid_key, base_url).await?;
+
+        let tcp_server = match &config.app.tcp_listener {
+            Some(tcp_config) => {
+                let listener =
+                    tokio::net::TcpListener::bind(format!("127.0.0.1:{}", tcp_config.port)).await?;
+                Some(TcpPasteServer::new(
+                    listener,
+                    db_pool.clone(),
+                    store.clone(),
+                    config.app.base_url.clone(),
+                    config.app.max_paste_size,
+                    config.app.max_expiry,
+                    ConnectionLimits {
+                        idle_timeout: Duration::from_secs(tcp_config.idle_timeout),
+                        read_timeout: Duration::from_secs(tcp_config.read_timeout),
+                        max_connections: tcp_config.max_connections,
+                    },
+                ))
+            }
+            None => None,
+        };
+        let tcp_port = tcp_server.as_ref().map(|s| s.port()).transpose()?;
+
+        let reaper = tokio::spawn(reap_expired_pastes(
+            db_pool.clone(),
+            store.clone(),
+            Duration::from_secs(config.app.reaper_interval),
+        ));
+
+        let server = run(socket, db_pool, store, config.app).await?;
         Ok(Self {
             port,
+            tcp_port,
             actix_server: server,
+            tcp_server,
+            reaper,
         })
     }
 
+    /// Run the application until the actix server shuts down.
+    ///
+    /// The tcp paste listener, if enabled, runs alongside the actix server
+    /// and is shut down together with it, as is the task that deletes expired pastes.
     pub async fn run(self) -> Result<(), std::io::Error> {
-        self.actix_server.await
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let tcp_task = self.tcp_server.map(|tcp_server| {
+            tokio::spawn(tcp_server.run(async {
+                let _ = shutdown_rx.await;
+            }))
+        });
+
+        let result = self.actix_server.await;
+        self.reaper.abort();
+        let _ = shutdown_tx.send(());
+        if let Some(tcp_task) = tcp_task {
+            if let Err(e) = tcp_task.await {
+                tracing::error!("tcp paste listener failed: {}", e);
+            }
+        }
+        result
     }
 }
 
@@ -57,6 +131,25 @@ pub struct SendGridApiKey(pub Secret<String>);
 
 pub struct Pepper(pub Secret<String>);
 
+/// Maximum size of a paste in bytes.
+#[derive(Debug, Copy, Clone)]
+pub struct MaxPasteSize(pub usize);
+
+/// Maximum size of a binary attachment in bytes.
+#[derive(Debug, Copy, Clone)]
+pub struct MaxAttachmentSize(pub usize);
+
+/// Longest lifetime a paste may have.
+#[derive(Debug, Copy, Clone)]
+pub struct MaxExpiry(pub Expiry);
+
+impl MaxExpiry {
+    /// Cap the lifetime requested for a paste, using the maximum if none was requested.
+    pub fn cap(&self, requested: Option<Expiry>) -> Expiry {
+        requested.map_or(self.0, |expiry| expiry.min(self.0))
+    }
+}
+
 /// Construct the actix server instance based on the passed parameters.
 ///
 /// The actix server gets built here, supplying every information necessary (routes, app data, etc.).
@@ -64,35 +157,99 @@ pub struct Pepper(pub Secret<String>);
 ///
 /// * `socket` - [`TcpListener`] to bind the server to
 /// * `db_pool` - [`PgPool`] to use for data storage
-/// * `pepper` - String that gets added to Password Hashes. For further information see [Pepper](https://en.wikipedia.org/wiki/Pepper_(cryptography))
+/// * `store` - [`BlobStore`] holding the content of pastes
+/// * `config` - [`AppConfig`] holding the pepper that gets added to password hashes (for further information see
+///   [Pepper](https://en.wikipedia.org/wiki/Pepper_(cryptography))), size limits and other settings
 async fn run(
     socket: TcpListener,
     db_pool: PgPool,
-    pepper: Secret<String>,
-    sendgrid_key: Secret<String>,
-    base_url: String,
+    store: Arc<dyn BlobStore>,
+    config: AppConfig,
 ) -> Result<Server, anyhow::Error> {
     log::configure_subscriber();
 
+    let max_paste_size = config.max_paste_size;
     let db_pool = Data::new(db_pool);
-    let pepper = Data::new(Pepper(pepper));
-    let sendgrid = Data::new(SendGridApiKey(sendgrid_key));
-    let base = Data::new(AppBaseUrl(base_url));
+    let store = Data::from(store);
+    let pepper = Data::new(Pepper(config.pepper));
+    let sendgrid = Data::new(SendGridApiKey(config.sendgrid_key));
+    let base = Data::new(AppBaseUrl(config.base_url));
+    let max_size = Data::new(MaxPasteSize(max_paste_size));
+    let max_attachment_size = Data::new(MaxAttachmentSize(config.max_attachment_size));
+    let max_expiry = Data::new(MaxExpiry(config.max_expiry));
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
             .route("/", web::get().to(index_page))
+            .route("/", web::post().to(upload_paste))
             .route("/healthcheck", web::get().to(health_check))
             .route("/register", web::get().to(register))
+            .route("/login", web::get().to(login))
+            .route("/reset", web::get().to(reset))
+            .route("/encrypt", web::get().to(new_encrypted_paste))
             .route("/notfound", web::route().to(not_found))
             .route("/activate", web::route().to(activate_user))
-            .service(web::scope("api").route("/register", web::post().to(register_user)))
-            .app_data(web::JsonConfig::default().error_handler(json_deserialize_error_handler))
+            .service(
+                web::scope("api")
+                    .route("/register", web::post().to(register_user))
+                    .route("/login", web::post().to(login_user))
+                    .route("/logout", web::post().to(logout_user))
+                    .route("/activation/resend", web::post().to(resend_activation))
+                    .route("/password/forgot", web::post().to(forgot_password))
+                    .route("/password/reset", web::post().to(reset_password))
+                    .route("/tokens", web::get().to(list_tokens))
+                    .route("/tokens", web::post().to(create_token))
+                    .route("/tokens/{id}", web::delete().to(delete_token))
+                    .route("/pastes", web::get().to(list_pastes))
+                    .route("/pastes", web::post().to(create_paste))
+                    .route("/pastes/{id}", web::get().to(get_paste))
+                    .route("/pastes/{id}", web::patch().to(update_paste))
+                    .route("/pastes/{id}", web::delete().to(delete_paste))
+                    .route("/pastes/{id}/fork", web::post().to(fork_paste))
+                    .route("/pastes/{id}/forks", web::get().to(list_forks))
+                    .route("/pastes/{id}/attachments", web::post().to(add_attachment))
+                    .route(
+                        "/pastes/{id}/attachments/{filename}",
+                        web::delete().to(delete_attachment),
+                    ),
+            )
+            .app_data(
+                web::JsonConfig::default()
+                    // leave room for escape sequences in the json encoded paste content
+                    .limit(max_paste_size * 2)
+                    .error_handler(json_deserialize_error_handler),
+            )
             .service(Files::new("/static", "./static").prefer_utf8(true))
+            .route("/{id}/raw", web::get().to(raw_paste))
+            .route("/{id}/raw", web::post().to(burn_raw_paste))
+            .route("/{id}/raw/{filename}", web::get().to(raw_paste_file))
+            .route("/{id}/download.{format}", web::get().to(download_paste))
+            .route(
+                "/{id}/download.{format}",
+                web::post().to(burn_download_paste),
+            )
+            .route(
+                "/{id}/attachments/{filename}",
+                web::get().to(view_attachment),
+            )
+            .route("/{id}/revisions", web::get().to(paste_revisions))
+            .route("/{id}/unlock", web::post().to(unlock_paste))
+            .route("/{id}/fork", web::post().to(create_fork))
+            .route("/{id}/forks", web::get().to(forks_page))
+            .route("/{id}/diff", web::get().to(select_diff))
+            .route("/{id}/diff/{a}/{b}", web::get().to(paste_diff))
+            .route("/{id}", web::get().to(view_paste))
+            .route("/{id}", web::post().to(burn_paste))
+            .route("/{username}/{slug}", web::get().to(vanity_paste))
+            .route("/{username}/{slug}/{tail:.*}", web::get().to(vanity_paste))
             .app_data(db_pool.clone())
+            .app_data(store.clone())
             .app_data(pepper.clone())
             .app_data(sendgrid.clone())
             .app_data(base.clone())
+            .app_data(max_size.clone())
+            .app_data(max_attachment_size.clone())
+            .app_data(max_expiry.clone())
     })
     .listen(socket)?
     .run();
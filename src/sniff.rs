@@ -0,0 +1,157 @@
+//! Detection of the media type of uploads from their content.
+//!
+//! The media type claimed by clients is never trusted. Instead the leading bytes of an upload
+//! are compared against the signatures of common binary formats.
+
+/// Media type of attachments whose format is unknown.
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Signature of a binary format, consisting of the bytes expected at `offset`.
+struct Signature {
+    offset: usize,
+    magic: &'static [u8],
+    mime_type: &'static str,
+    extension: &'static str,
+}
+
+const fn signature(
+    offset: usize,
+    magic: &'static [u8],
+    mime_type: &'static str,
+    extension: &'static str,
+) -> Signature {
+    Signature {
+        offset,
+        magic,
+        mime_type,
+        extension,
+    }
+}
+
+/// Known signatures. More specific signatures have to come before shorter ones they share a prefix with.
+const SIGNATURES: &[Signature] = &[
+    signature(0, b"\x89PNG\r\n\x1a\n", "image/png", "png"),
+    signature(0, b"\xff\xd8\xff", "image/jpeg", "jpg"),
+    signature(0, b"GIF87a", "image/gif", "gif"),
+    signature(0, b"GIF89a", "image/gif", "gif"),
+    signature(8, b"WEBP", "image/webp", "webp"),
+    signature(0, b"\x00\x00\x01\x00", "image/x-icon", "ico"),
+    signature(0, b"%PDF-", "application/pdf", "pdf"),
+    signature(0, b"PK\x03\x04", "application/zip", "zip"),
+    signature(0, b"PK\x05\x06", "application/zip", "zip"),
+    signature(0, b"\x1f\x8b", "application/gzip", "gz"),
+    signature(0, b"BZh", "application/x-bzip2", "bz2"),
+    signature(0, b"\xfd7zXZ\x00", "application/x-xz", "xz"),
+    signature(0, b"\x28\xb5\x2f\xfd", "application/zstd", "zst"),
+    signature(
+        0,
+        b"7z\xbc\xaf\x27\x1c",
+        "application/x-7z-compressed",
+        "7z",
+    ),
+    signature(0, b"Rar!\x1a\x07", "application/vnd.rar", "rar"),
+    signature(257, b"ustar", "application/x-tar", "tar"),
+    signature(0, b"\x7fELF", "application/x-executable", "elf"),
+    signature(0, b"\x00asm", "application/wasm", "wasm"),
+    signature(0, b"ID3", "audio/mpeg", "mp3"),
+    signature(0, b"fLaC", "audio/flac", "flac"),
+    signature(0, b"OggS", "audio/ogg", "ogg"),
+    signature(8, b"WAVE", "audio/wav", "wav"),
+    signature(4, b"ftyp", "video/mp4", "mp4"),
+    signature(0, b"\x1a\x45\xdf\xa3", "video/webm", "webm"),
+];
+
+/// Detect the media type of `data` from the signature of its format.
+///
+/// Returns `None` if the format is not known, which includes all kinds of text.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    find(data).map(|signature| signature.mime_type)
+}
+
+/// Media type under which `data` is stored and served.
+///
+/// Unknown formats are served as `application/octet-stream`, so browsers never render them.
+pub fn media_type(data: &[u8]) -> &'static str {
+    sniff(data).unwrap_or(OCTET_STREAM)
+}
+
+/// File extension matching the format of `data`, used to name uploads that come without a filename.
+pub fn extension(data: &[u8]) -> &'static str {
+    find(data).map_or("bin", |signature| signature.extension)
+}
+
+/// Whether `data` is text that can be stored as a regular file of a paste.
+pub fn is_text(data: &[u8]) -> bool {
+    sniff(data).is_none() && !data.contains(&0) && std::str::from_utf8(data).is_ok()
+}
+
+/// Whether attachments of the given media type may be shown inline in the browser.
+///
+/// Only raster images qualify. SVG images are plain text to the sniffer and could carry scripts anyway.
+pub fn is_inline_image(mime_type: &str) -> bool {
+    matches!(
+        mime_type,
+        "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "image/x-icon"
+    )
+}
+
+fn find(data: &[u8]) -> Option<&'static Signature> {
+    SIGNATURES.iter().find(|signature| {
+        data.get(signature.offset..signature.offset + signature.magic.len())
+            == Some(signature.magic)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_images() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("image/jpeg"));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+    }
+
+    #[test]
+    fn detects_documents_and_archives() {
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff(b"PK\x03\x04\x14\0"), Some("application/zip"));
+        assert_eq!(sniff(b"\x1f\x8b\x08\0"), Some("application/gzip"));
+    }
+
+    #[test]
+    fn detects_tar_at_offset() {
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+
+        assert_eq!(sniff(&tar), Some("application/x-tar"));
+    }
+
+    #[test]
+    fn ignores_claimed_types_of_text() {
+        assert_eq!(sniff(b"<svg onload=\"alert(1)\"></svg>"), None);
+        assert_eq!(media_type(b"<html></html>"), OCTET_STREAM);
+        assert!(is_text("fn main() {}\n".as_bytes()));
+    }
+
+    #[test]
+    fn binary_is_not_text() {
+        assert!(!is_text(b"\x89PNG\r\n\x1a\n"));
+        assert!(!is_text(b"abc\0def"));
+        assert!(!is_text(b"\xff\xfe"));
+    }
+
+    #[test]
+    fn extension_of_unknown_format() {
+        assert_eq!(extension(b"\x01\x02\x03"), "bin");
+        assert_eq!(extension(b"%PDF-1.4"), "pdf");
+    }
+
+    #[test]
+    fn only_raster_images_are_inline() {
+        assert!(is_inline_image("image/png"));
+        assert!(!is_inline_image("image/svg+xml"));
+        assert!(!is_inline_image("application/pdf"));
+    }
+}
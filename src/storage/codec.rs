@@ -0,0 +1,138 @@
+use crate::config::CompressionConfig;
+use flate2::{read::GzDecoder, write::GzEncoder};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+/// Level used for zstd, its default balance of speed and ratio.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression applied to a blob before it is stored.
+///
+/// The codec of every blob is recorded in `pastr.blob_refs`, so blobs stored with different
+/// codecs can be read side by side after the configured codec changed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Stored as is
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    /// Return a string representation of the codec, as it is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Value of the `Content-Encoding` header for data compressed with the codec.
+    /// `None` for uncompressed data.
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gzip"),
+            Self::Zstd => Some("zstd"),
+        }
+    }
+
+    /// Compress `data` with the codec.
+    pub fn encode<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>, std::io::Error> {
+        Ok(match self {
+            Self::None => Cow::Borrowed(data),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Cow::Owned(encoder.finish()?)
+            }
+            Self::Zstd => Cow::Owned(zstd::encode_all(data, ZSTD_LEVEL)?),
+        })
+    }
+
+    /// Decompress `data` that was compressed with the codec.
+    pub fn decode(&self, data: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::None => Ok(data),
+            Self::Gzip => {
+                let mut decoded = Vec::with_capacity(data.len() * 4);
+                GzDecoder::new(&data[..]).read_to_end(&mut decoded)?;
+                Ok(decoded)
+            }
+            Self::Zstd => zstd::decode_all(&data[..]),
+        }
+    }
+}
+
+/// Compress `data` as configured, returning the codec that was used together with the compressed data.
+///
+/// Content below the configured minimum size, or content that does not get smaller, like images
+/// or archives, is returned as is.
+pub fn compress<'a>(
+    config: &CompressionConfig,
+    data: &'a [u8],
+) -> Result<(Codec, Cow<'a, [u8]>), std::io::Error> {
+    if data.len() < config.min_size {
+        return Ok((Codec::None, Cow::Borrowed(data)));
+    }
+
+    let encoded = config.codec.encode(data)?;
+    match encoded.len() < data.len() {
+        true => Ok((config.codec, encoded)),
+        false => Ok((Codec::None, Cow::Borrowed(data))),
+    }
+}
+
+impl TryFrom<&str> for Codec {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "none" => Ok(Self::None),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            other => Err(format!("{} is not a valid blob codec", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codecs_round_trip() {
+        let data = "ci line\n".repeat(1000).into_bytes();
+
+        for codec in [Codec::None, Codec::Gzip, Codec::Zstd] {
+            let encoded = codec.encode(&data).unwrap().into_owned();
+            assert_eq!(codec.decode(encoded).unwrap(), data, "{codec:?}");
+        }
+    }
+
+    #[test]
+    fn compresses_only_when_worthwhile() {
+        let config = CompressionConfig {
+            codec: Codec::Gzip,
+            min_size: 64,
+        };
+        let log = "ci line\n".repeat(100).into_bytes();
+
+        assert_eq!(compress(&config, b"short").unwrap().0, Codec::None);
+        assert_eq!(compress(&config, &log).unwrap().0, Codec::Gzip);
+        // already compressed data does not shrink any further
+        let gzipped = Codec::Gzip.encode(&log).unwrap().into_owned();
+        assert_eq!(compress(&config, &gzipped).unwrap().0, Codec::None);
+    }
+
+    #[test]
+    fn codec_names_round_trip() {
+        for codec in [Codec::None, Codec::Gzip, Codec::Zstd] {
+            assert_eq!(Codec::try_from(codec.as_str()), Ok(codec));
+        }
+        assert!(Codec::try_from("brotli").is_err());
+    }
+}
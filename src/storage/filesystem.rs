@@ -0,0 +1,105 @@
+use super::{is_valid_key, BlobStore};
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Stores every blob as a file below a directory on the local filesystem.
+///
+/// Blobs are spread over subdirectories named after the first two characters of their key,
+/// so no single directory ends up with millions of entries.
+#[derive(Debug, Clone)]
+pub struct FilesystemBlobStore {
+    root: PathBuf,
+}
+
+impl FilesystemBlobStore {
+    /// * `root` - directory that holds the blobs. Created on the first write if it does not exist
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Path of the file that holds the blob stored under `key`.
+    fn path(&self, key: &str) -> Result<PathBuf, anyhow::Error> {
+        if !is_valid_key(key) {
+            anyhow::bail!("invalid blob key {:?}", key);
+        }
+        Ok(self.root.join(&key[..key.len().min(2)]).join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for FilesystemBlobStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+        let path = self.path(key)?;
+        let directory = path.parent().expect("blob paths have a parent directory");
+        tokio::fs::create_dir_all(directory).await?;
+
+        // write to a temporary file first, so readers never see a partially written blob
+        let temporary = directory.join(format!(".{}.tmp", Uuid::new_v4().simple()));
+        if let Err(e) = tokio::fs::write(&temporary, data).await {
+            let _ = tokio::fs::remove_file(&temporary).await;
+            return Err(e.into());
+        }
+        tokio::fs::rename(&temporary, &path).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::content_key;
+
+    fn store() -> FilesystemBlobStore {
+        FilesystemBlobStore::new(
+            std::env::temp_dir().join(format!("pastr-test-{}", Uuid::new_v4().simple())),
+        )
+    }
+
+    #[tokio::test]
+    async fn stores_and_deletes_blobs() {
+        let store = store();
+        let key = content_key(b"fn main() {}\n");
+
+        store.put(&key, b"fn main() {}\n").await.unwrap();
+        assert_eq!(
+            store.get(&key).await.unwrap().as_deref(),
+            Some(&b"fn main() {}\n"[..])
+        );
+
+        store.delete(&key).await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), None);
+        tokio::fs::remove_dir_all(&store.root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deleting_missing_blob_succeeds() {
+        assert!(store().delete(&content_key(b"missing")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_path_like_keys() {
+        let store = store();
+
+        assert!(store.get("../../etc/passwd").await.is_err());
+        assert!(store.put("", b"data").await.is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Storage of paste content outside of the regular database tables.
+//!
+//! The database only holds the metadata of files and attachments together with a key,
+//! the content itself lives in a [`BlobStore`]. Which one is used is chosen by the `storage`
+//! section of the config, see [`StorageConfig`].
+use crate::config::{CompressionConfig, StorageConfig};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+mod codec;
+mod filesystem;
+mod postgres;
+mod s3;
+
+pub use codec::{compress, Codec};
+pub use filesystem::FilesystemBlobStore;
+pub use postgres::PostgresBlobStore;
+pub use s3::S3BlobStore;
+
+/// Backend that stores the content of pastes as opaque blobs, addressed by a key.
+///
+/// Keys are derived from the content via [`content_key`] and only consist of ascii letters and digits.
+/// Blobs stored before that use random keys of the same alphabet.
+#[async_trait]
+pub trait BlobStore: std::fmt::Debug + Send + Sync {
+    /// Store `data` under `key`, replacing any blob that was stored under the same key.
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), anyhow::Error>;
+
+    /// Load the blob stored under `key`. Returns `None` if there is no such blob.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error>;
+
+    /// Remove the blob stored under `key`. Removing a blob that does not exist is not an error.
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error>;
+
+    /// How new blobs should be compressed before they are put, see [`compress`].
+    ///
+    /// Stores built by [`connect`] use the configured compression, others store blobs uncompressed.
+    fn compression(&self) -> CompressionConfig {
+        CompressionConfig::NONE
+    }
+}
+
+/// Key to store `data` under, the hex encoded SHA-256 hash of it.
+///
+/// Identical content always ends up under the same key, so it is only stored once.
+pub fn content_key(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Build the [`BlobStore`] selected by the config.
+///
+/// * `config` - selected backend and its settings
+/// * `compression` - compression applied to new blobs
+/// * `pool` - [`PgPool`] used by the Postgres backend
+pub fn connect(
+    config: &StorageConfig,
+    compression: CompressionConfig,
+    pool: PgPool,
+) -> Result<Arc<dyn BlobStore>, anyhow::Error> {
+    let backend: Box<dyn BlobStore> = match config {
+        StorageConfig::Postgres => Box::new(PostgresBlobStore::new(pool)),
+        StorageConfig::Filesystem { path } => Box::new(FilesystemBlobStore::new(path.clone())),
+        StorageConfig::S3(config) => Box::new(S3BlobStore::new(config)?),
+    };
+    Ok(Arc::new(Compressed {
+        backend,
+        compression,
+    }))
+}
+
+/// A backend together with the compression that the configuration asks for.
+///
+/// Does not compress anything itself, callers compress blobs as it says and record the codec.
+#[derive(Debug)]
+struct Compressed {
+    backend: Box<dyn BlobStore>,
+    compression: CompressionConfig,
+}
+
+#[async_trait]
+impl BlobStore for Compressed {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+        self.backend.put(key, data).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        self.backend.get(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error> {
+        self.backend.delete(key).await
+    }
+
+    fn compression(&self) -> CompressionConfig {
+        self.compression
+    }
+}
+
+/// Whether `key` could be the key of a blob.
+///
+/// Backends that map keys to paths check this, so a key can never escape their directory.
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_key_is_sha256_of_content() {
+        assert_eq!(
+            content_key(b"hello\n"),
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+        );
+        assert!(is_valid_key(&content_key(b"")));
+    }
+
+    #[test]
+    fn identical_content_shares_key() {
+        assert_eq!(content_key(b"same log"), content_key(b"same log"));
+        assert_ne!(content_key(b"same log"), content_key(b"other log"));
+    }
+}
@@ -0,0 +1,52 @@
+use super::BlobStore;
+use async_trait::async_trait;
+use sqlx::{PgPool, Row};
+
+/// Stores blobs as `bytea` in the `pastr.blobs` table of the application database.
+///
+/// Needs no further setup, which makes it the default. Large instances should use
+/// one of the other backends, so the content does not bloat the database.
+#[derive(Debug, Clone)]
+pub struct PostgresBlobStore {
+    pool: PgPool,
+}
+
+impl PostgresBlobStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl BlobStore for PostgresBlobStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "INSERT INTO pastr.blobs (key, data) VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE SET data = EXCLUDED.data;",
+        )
+        .bind(key)
+        .bind(data)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let row = sqlx::query("SELECT data FROM pastr.blobs WHERE key = $1;")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| row.try_get("data")).transpose()?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error> {
+        sqlx::query("DELETE FROM pastr.blobs WHERE key = $1;")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
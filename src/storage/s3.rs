@@ -0,0 +1,62 @@
+use super::BlobStore;
+use crate::config::S3Config;
+use async_trait::async_trait;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use secrecy::ExposeSecret;
+
+/// Stores blobs as objects in a bucket of an S3-compatible object storage, like AWS S3 or MinIO.
+///
+/// Objects are named after their key and placed at the root of the bucket.
+#[derive(Debug)]
+pub struct S3BlobStore {
+    bucket: AmazonS3,
+}
+
+impl S3BlobStore {
+    /// Build a client for the configured bucket. Does not connect to the storage yet.
+    pub fn new(config: &S3Config) -> Result<Self, anyhow::Error> {
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            .with_access_key_id(&config.access_key_id)
+            .with_secret_access_key(config.secret_access_key.expose_secret());
+        if let Some(endpoint) = &config.endpoint {
+            // self-hosted storages like MinIO are often reached without tls inside the same network
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+
+        Ok(Self {
+            bucket: builder.build()?,
+        })
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+        self.bucket
+            .put(&Path::from(key), PutPayload::from(data.to_vec()))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        match self.bucket.get(&Path::from(key)).await {
+            Ok(object) => Ok(Some(object.bytes().await?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), anyhow::Error> {
+        match self.bucket.delete(&Path::from(key)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
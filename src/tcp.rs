@@ -0,0 +1,306 @@
+use crate::entity::{Expiry, NewFile, NewPaste, Paste, PasteKind, Visibility};
+use crate::storage::BlobStore;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+#[derive(Debug, thiserror::Error)]
+enum TcpPasteError {
+    #[error("paste exceeds the maximum size of {0} bytes")]
+    TooLarge(usize),
+    #[error("paste must not be empty")]
+    Empty,
+    #[error("paste must be valid utf-8 text")]
+    NotUtf8,
+    #[error("paste was not completed within {} seconds", .0.as_secs())]
+    TimedOut(Duration),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Limits that keep slow or numerous clients from tying up the [`TcpPasteServer`].
+#[derive(Debug, Copy, Clone)]
+pub struct ConnectionLimits {
+    /// Time without new data after which a paste is considered complete
+    pub idle_timeout: Duration,
+    /// Time after which a connection is closed, however steadily the client sends data
+    pub read_timeout: Duration,
+    /// Connections that are handled at the same time, further connections are closed right away
+    pub max_connections: usize,
+}
+
+/// Listener that accepts pastes over plain tcp connections, in the style of termbin.
+///
+/// Everything sent over a connection until EOF, the size limit or an idle timeout is stored as a paste.
+/// The url of the new paste is written back before the connection gets closed,
+/// so `echo foo | nc pastr 9999` prints the link to the paste.
+pub struct TcpPasteServer {
+    listener: TcpListener,
+    pool: PgPool,
+    store: Arc<dyn BlobStore>,
+    base_url: String,
+    max_size: usize,
+    expiry: Expiry,
+    limits: ConnectionLimits,
+}
+
+impl TcpPasteServer {
+    /// Create a server that accepts connections on the given `listener`.
+    ///
+    /// * `listener` - bound [`TcpListener`] to accept connections on
+    /// * `pool` - [`PgPool`] to store pastes in
+    /// * `store` - [`BlobStore`] to store the content of pastes in
+    /// * `base_url` - base url of the service, used to construct the returned links
+    /// * `max_size` - maximum size of a paste in bytes
+    /// * `expiry` - lifetime of the created pastes
+    /// * `limits` - timeouts and number of concurrent connections
+    pub fn new(
+        listener: TcpListener,
+        pool: PgPool,
+        store: Arc<dyn BlobStore>,
+        base_url: String,
+        max_size: usize,
+        expiry: Expiry,
+        limits: ConnectionLimits,
+    ) -> Self {
+        Self {
+            listener,
+            pool,
+            store,
+            base_url,
+            max_size,
+            expiry,
+            limits,
+        }
+    }
+
+    /// Port the server is listening on.
+    pub fn port(&self) -> Result<u16, std::io::Error> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Accept connections until `shutdown` completes.
+    ///
+    /// Connections that are still being handled when `shutdown` completes are allowed to finish,
+    /// which takes at most the read timeout.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) {
+        let mut connections = JoinSet::new();
+        let permits = Arc::new(Semaphore::new(self.limits.max_connections));
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        let Ok(permit) = permits.clone().try_acquire_owned() else {
+                            tracing::warn!("turned away tcp paste connection from {}, too many connections", peer);
+                            continue;
+                        };
+                        tracing::debug!("accepted tcp paste connection from {}", peer);
+                        let connection = handle_connection(
+                            stream,
+                            self.pool.clone(),
+                            self.store.clone(),
+                            self.base_url.clone(),
+                            self.max_size,
+                            self.expiry,
+                            self.limits,
+                        );
+                        connections.spawn(async move {
+                            connection.await;
+                            drop(permit);
+                        });
+                    }
+                    Err(e) => {
+                        tracing::error!("failed to accept tcp paste connection: {}", e);
+                        // back off in case we ran out of file descriptors
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                },
+                // reap finished connections so the set does not grow unbounded
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            }
+        }
+
+        while connections.join_next().await.is_some() {}
+    }
+}
+
+/// Store everything sent over `stream` as a paste and respond with its url.
+#[tracing::instrument(name = "TCP Paste Request", skip_all)]
+async fn handle_connection(
+    mut stream: TcpStream,
+    pool: PgPool,
+    store: Arc<dyn BlobStore>,
+    base_url: String,
+    max_size: usize,
+    expiry: Expiry,
+    limits: ConnectionLimits,
+) {
+    let response = match read_paste(&mut stream, max_size, &limits).await {
+        Ok(content) => {
+            let paste = NewPaste {
+                owner: None,
+                title: None,
+                files: vec![NewFile {
+                    filename: None,
+                    content: &content,
+                    language: None,
+                }],
+                attachments: Vec::new(),
+                expires_at: expiry.expires_at(Utc::now()),
+                burn_after_reading: false,
+                visibility: Visibility::Unlisted,
+                password_hash: None,
+                kind: PasteKind::Plain,
+                slug: None,
+            };
+            match Paste::create(&paste, &pool, store.as_ref()).await {
+                Ok(created) => format!("https://{}/{}\n", base_url, created.short_id),
+                Err(e) => {
+                    tracing::error!("error while storing tcp paste: {:?}", e);
+                    "error while processing request\n".to_owned()
+                }
+            }
+        }
+        Err(TcpPasteError::Io(e)) => {
+            tracing::debug!("error reading tcp paste: {}", e);
+            return;
+        }
+        Err(e) => format!("{}\n", e),
+    };
+
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        tracing::debug!("error writing tcp paste response: {}", e);
+        return;
+    }
+    let _ = stream.shutdown().await;
+}
+
+/// Read the content of a paste from `reader`.
+///
+/// Reading stops at EOF or once no data arrived for the idle timeout, since some `nc` variants
+/// keep the connection open after their input is exhausted. Clients that are still sending
+/// when the read timeout passes are rejected.
+async fn read_paste<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_size: usize,
+    limits: &ConnectionLimits,
+) -> Result<String, TcpPasteError> {
+    let mut content = Vec::new();
+    let mut buf = [0u8; 8192];
+
+    let read_all = async {
+        loop {
+            let read = match tokio::time::timeout(limits.idle_timeout, reader.read(&mut buf)).await
+            {
+                Ok(read) => read?,
+                Err(_) => break,
+            };
+            if read == 0 {
+                break;
+            }
+            if content.len() + read > max_size {
+                return Err(TcpPasteError::TooLarge(max_size));
+            }
+            content.extend_from_slice(&buf[..read]);
+        }
+        Ok(())
+    };
+    tokio::time::timeout(limits.read_timeout, read_all)
+        .await
+        .map_err(|_| TcpPasteError::TimedOut(limits.read_timeout))??;
+
+    let content = String::from_utf8(content).map_err(|_| TcpPasteError::NotUtf8)?;
+    if content.trim().is_empty() {
+        return Err(TcpPasteError::Empty);
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: ConnectionLimits = ConnectionLimits {
+        idle_timeout: Duration::from_secs(1),
+        read_timeout: Duration::from_secs(5),
+        max_connections: 1,
+    };
+
+    #[tokio::test]
+    async fn read_until_eof() {
+        let mut input: &[u8] = b"echo foo\n";
+        let content = read_paste(&mut input, 1024, &LIMITS)
+            .await
+            .expect("reading paste failed");
+
+        assert_eq!(content, "echo foo\n");
+    }
+
+    #[tokio::test]
+    async fn read_until_idle() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"foo").await.unwrap();
+
+        // keep the client alive, so only the idle timeout can end the paste
+        let limits = ConnectionLimits {
+            idle_timeout: Duration::from_millis(50),
+            ..LIMITS
+        };
+        let content = read_paste(&mut server, 1024, &limits)
+            .await
+            .expect("reading paste failed");
+        drop(client);
+
+        assert_eq!(content, "foo");
+    }
+
+    #[tokio::test]
+    async fn reject_too_large() {
+        let mut input: &[u8] = b"0123456789";
+        let result = read_paste(&mut input, 5, &LIMITS).await;
+
+        assert!(matches!(result, Err(TcpPasteError::TooLarge(5))));
+    }
+
+    #[tokio::test]
+    async fn reject_empty() {
+        let mut input: &[u8] = b"  \n";
+        let result = read_paste(&mut input, 1024, &LIMITS).await;
+
+        assert!(matches!(result, Err(TcpPasteError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn reject_trickling_client() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let limits = ConnectionLimits {
+            idle_timeout: Duration::from_millis(50),
+            read_timeout: Duration::from_millis(200),
+            ..LIMITS
+        };
+
+        // send a byte before every idle timeout, so only the read timeout can end the paste
+        let sender = tokio::spawn(async move {
+            for _ in 0..20 {
+                if client.write_all(b"a").await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        let result = read_paste(&mut server, 1024, &limits).await;
+        drop(server);
+        sender.await.unwrap();
+
+        assert!(matches!(result, Err(TcpPasteError::TimedOut(_))));
+    }
+}
@@ -0,0 +1,140 @@
+// Client side encryption of pastes.
+//
+// Pastes are encrypted with AES-GCM in the browser before they are uploaded, so the server only ever
+// stores ciphertext. The key is kept in the fragment of the paste url, which browsers never send to the server.
+//
+// The content of an encrypted paste is the base64 encoded 12 byte nonce followed by the ciphertext.
+// It decrypts to a JSON object holding the `title` and `content` of the paste.
+
+const NONCE_LENGTH = 12;
+
+const toBase64 = (bytes) => {
+  let binary = '';
+  // String.fromCharCode takes its arguments from the stack, so large pastes are converted in chunks
+  for (let i = 0; i < bytes.length; i += 0x8000) {
+    binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+  }
+  return btoa(binary);
+};
+
+const fromBase64 = (text) => Uint8Array.from(atob(text), (c) => c.charCodeAt(0));
+
+// keys end up in urls, so they use the url safe alphabet without padding
+const toBase64Url = (bytes) =>
+  toBase64(bytes).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+
+const fromBase64Url = (text) => {
+  const base64 = text.replace(/-/g, '+').replace(/_/g, '/');
+  return fromBase64(base64.padEnd(Math.ceil(base64.length / 4) * 4, '='));
+};
+
+// Encrypt a paste with a fresh key. Returns the content to upload and the key for the url fragment.
+export const encrypt = async (paste) => {
+  const key = await crypto.subtle.generateKey({ name: 'AES-GCM', length: 256 }, true, ['encrypt']);
+  const nonce = crypto.getRandomValues(new Uint8Array(NONCE_LENGTH));
+  const plaintext = new TextEncoder().encode(JSON.stringify(paste));
+  const ciphertext = await crypto.subtle.encrypt({ name: 'AES-GCM', iv: nonce }, key, plaintext);
+
+  const data = new Uint8Array(NONCE_LENGTH + ciphertext.byteLength);
+  data.set(nonce);
+  data.set(new Uint8Array(ciphertext), NONCE_LENGTH);
+  const rawKey = new Uint8Array(await crypto.subtle.exportKey('raw', key));
+  return { content: toBase64(data), key: toBase64Url(rawKey) };
+};
+
+// Decrypt the content of an encrypted paste. Fails if the key is wrong or the content was tampered with.
+export const decrypt = async (content, key) => {
+  const data = fromBase64(content);
+  const cryptoKey = await crypto.subtle.importKey('raw', fromBase64Url(key), 'AES-GCM', false, ['decrypt']);
+  const plaintext = await crypto.subtle.decrypt(
+    { name: 'AES-GCM', iv: data.subarray(0, NONCE_LENGTH) },
+    cryptoKey,
+    data.subarray(NONCE_LENGTH),
+  );
+  return JSON.parse(new TextDecoder().decode(plaintext));
+};
+
+const showAlert = (placeholder, kind, message) => {
+  const alert = document.createElement('div');
+  alert.className = `alert alert-${kind}`;
+  alert.textContent = message;
+  placeholder.replaceChildren(alert);
+};
+
+const createPaste = async (form) => {
+  const alert = form.querySelector('#alert-placeholder');
+  const title = form.querySelector('#title').value.trim();
+  const { content, key } = await encrypt({
+    title: title || null,
+    content: form.querySelector('#content').value,
+  });
+
+  const response = await fetch(window.location.origin.concat('/api/pastes'), {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify({
+      kind: 'encrypted',
+      content,
+      expires: form.querySelector('#expires').value,
+      burn_after_reading: form.querySelector('#burn').checked,
+      password: form.querySelector('#password').value || null,
+    }),
+  });
+  const response_json = await response.json();
+  if (response_json.success !== true) {
+    showAlert(alert, 'danger', `The paste could not be created: ${response_json.message}.`);
+    return;
+  }
+
+  const url = `${window.location.origin}/${response_json.data.id}#${key}`;
+  const link = form.querySelector('#paste-link');
+  link.href = url;
+  link.textContent = url;
+  form.querySelector('#edit-token').textContent = response_json.data.edit_token;
+  form.querySelector('#created').classList.remove('d-none');
+  alert.replaceChildren();
+};
+
+const showPaste = async (view) => {
+  const alert = view.querySelector('#alert-placeholder');
+  const key = window.location.hash.slice(1);
+  if (!key) {
+    showAlert(alert, 'danger', 'The link to this paste is missing its key, which follows the # of the url.');
+    return;
+  }
+
+  let paste;
+  try {
+    paste = await decrypt(view.dataset.ciphertext, key);
+  } catch (e) {
+    showAlert(alert, 'danger', 'The paste could not be decrypted. Check that the link is complete.');
+    return;
+  }
+  const title = paste.title || 'Untitled paste';
+  document.title = `Pastr - ${title}`;
+  view.querySelector('#paste-title').textContent = title;
+  view.querySelector('#paste-content').textContent = paste.content;
+  view.querySelector('#paste-content').classList.remove('d-none');
+};
+
+const form = document.querySelector('#encrypt-form');
+if (form) {
+  form.addEventListener('submit', (event) => {
+    event.preventDefault();
+    createPaste(form).catch((e) => {
+      showAlert(form.querySelector('#alert-placeholder'), 'danger', `The paste could not be encrypted: ${e}`);
+    });
+  });
+}
+
+const view = document.querySelector('#encrypted-paste');
+if (view) {
+  showPaste(view);
+}
+
+// forms that lead to a paste page, like the password prompt, have to keep the key in the fragment
+document.querySelectorAll('form[data-keep-fragment]').forEach((form) => {
+  form.action = form.getAttribute('action').concat(window.location.hash);
+});